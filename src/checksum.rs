@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+
+const READ_BUFFER_SIZE: usize = 1 << 20;
+
+/// What to do when an input file does not match the checksum manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MismatchPolicy {
+    /// Log the mismatch and import the file anyway
+    Warn,
+    /// Abort before importing anything
+    Refuse,
+}
+
+/// A list of SHA-256 checksums as published alongside the dumps.
+///
+/// Both the GNU `sha256sum` format (`<hash>  <name>`) and the BSD format
+/// (`SHA256 (<name>) = <hash>`) are accepted. Entries are keyed by file name only, as manifests
+/// usually list paths relative to the distributor's directory layout.
+#[derive(Debug, Default)]
+pub struct Manifest {
+    entries: HashMap<String, String>,
+}
+
+impl Manifest {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("unable to read checksum manifest {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("invalid checksum manifest {}", path.display()))
+    }
+
+    fn parse(contents: &str) -> Result<Self> {
+        let mut entries = HashMap::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, hash) = if let Some(rest) = line.strip_prefix("SHA256 (") {
+                rest.rsplit_once(") = ")
+                    .ok_or_else(|| anyhow!("malformed entry on line {}", line_number + 1))?
+            } else {
+                let (hash, name) = line
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| anyhow!("malformed entry on line {}", line_number + 1))?;
+                // sha256sum marks binary mode entries with a leading '*'
+                (name.trim_start().trim_start_matches('*'), hash)
+            };
+            if hash.len() != 64 || !hash.chars().all(|ch| ch.is_ascii_hexdigit()) {
+                return Err(anyhow!(
+                    "invalid SHA-256 digest on line {}",
+                    line_number + 1
+                ));
+            }
+            let name = Path::new(name)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| anyhow!("missing file name on line {}", line_number + 1))?;
+            entries.insert(name.to_string(), hash.to_ascii_lowercase());
+        }
+        Ok(Manifest { entries })
+    }
+
+    pub fn expected(&self, file: &Path) -> Option<&str> {
+        let name = file.file_name()?.to_str()?;
+        self.entries.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationStatus {
+    Match,
+    Mismatch,
+    /// The file has no entry in the manifest
+    Unlisted,
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifiedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+    pub expected: Option<String>,
+    pub status: VerificationStatus,
+}
+
+impl VerifiedFile {
+    pub fn is_match(&self) -> bool {
+        self.status == VerificationStatus::Match
+    }
+}
+
+pub fn sha256_file(path: &Path) -> Result<(u64, String)> {
+    let file = File::open(path).with_context(|| format!("unable to open {}", path.display()))?;
+    let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, file);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+pub fn verify_file(manifest: &Manifest, path: &Path) -> Result<VerifiedFile> {
+    let (size, sha256) = sha256_file(path)?;
+    let expected = manifest.expected(path).map(str::to_string);
+    let status = match &expected {
+        Some(expected) if *expected == sha256 => VerificationStatus::Match,
+        Some(_) => VerificationStatus::Mismatch,
+        None => VerificationStatus::Unlisted,
+    };
+    Ok(VerifiedFile {
+        path: path.to_path_buf(),
+        size,
+        sha256,
+        expected,
+        status,
+    })
+}
+
+/// Hash every file in `files` against `manifest`, using one thread per physical core.
+///
+/// Results are returned in the same order as `files`.
+pub fn verify_files(manifest: &Manifest, files: &[PathBuf]) -> Result<Vec<VerifiedFile>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(files.len()));
+    let num_threads = num_cpus::get_physical().min(files.len()).max(1);
+    thread::scope(|scope| {
+        for _ in 0..num_threads {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = files.get(index) else {
+                        break;
+                    };
+                    let result = verify_file(manifest, path);
+                    results.lock().expect("results lock").push((index, result));
+                }
+            });
+        }
+    });
+    let mut results = results.into_inner().expect("results lock");
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_parse_gnu_manifest() -> Result<()> {
+        let manifest = Manifest::parse(&format!(
+            "{EMPTY_SHA256}  comments/RC_2023-01.zst\n\n{}  *RS_2023-01.zst\n",
+            EMPTY_SHA256.to_uppercase()
+        ))?;
+        assert_eq!(
+            manifest.expected(Path::new("/data/RC_2023-01.zst")),
+            Some(EMPTY_SHA256)
+        );
+        assert_eq!(
+            manifest.expected(Path::new("RS_2023-01.zst")),
+            Some(EMPTY_SHA256)
+        );
+        assert_eq!(manifest.expected(Path::new("RS_2023-02.zst")), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_bsd_manifest() -> Result<()> {
+        let manifest = Manifest::parse(&format!("SHA256 (RC_2023-01.zst) = {EMPTY_SHA256}"))?;
+        assert_eq!(
+            manifest.expected(Path::new("RC_2023-01.zst")),
+            Some(EMPTY_SHA256)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_manifest() {
+        assert!(Manifest::parse("not-a-hash  RC_2023-01.zst").is_err());
+        assert!(Manifest::parse(EMPTY_SHA256).is_err());
+    }
+
+    #[test]
+    fn test_verify_file() -> Result<()> {
+        let path = Path::new("test_data/test_comments.json");
+        let (size, sha256) = sha256_file(path)?;
+        assert_eq!(size, fs::metadata(path)?.len());
+
+        let manifest = Manifest::parse(&format!("{sha256}  test_comments.json"))?;
+        let verified = verify_file(&manifest, path)?;
+        assert_eq!(verified.status, VerificationStatus::Match);
+
+        let manifest = Manifest::parse(&format!("{EMPTY_SHA256}  test_comments.json"))?;
+        let verified = verify_file(&manifest, path)?;
+        assert_eq!(verified.status, VerificationStatus::Mismatch);
+
+        let verified = verify_file(&Manifest::default(), path)?;
+        assert_eq!(verified.status, VerificationStatus::Unlisted);
+        Ok(())
+    }
+}
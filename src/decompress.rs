@@ -7,7 +7,7 @@ use std::path::Path;
 use anyhow::{Result, anyhow};
 use bzip2::read::BzDecoder;
 use fallible_streaming_iterator::FallibleStreamingIterator;
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 use xz2::read::XzDecoder;
 
 // represents the maximum distance as calculated by 2^log_distance for a decode window in zstd.
@@ -22,7 +22,7 @@ pub fn stream_lines(
         .ok_or_else(|| anyhow!("cannot the file extension for {}", filename.display()))?;
     if extension == "gz" {
         let file = File::open(filename)?;
-        let gzip_file = BufReader::new(GzDecoder::new(file));
+        let gzip_file = BufReader::new(MultiGzDecoder::new(file));
         return Ok(StreamingReader::new(gzip_file));
     } else if extension == "bz2" {
         let reader = fs::File::open(filename)?;
@@ -1,6 +1,56 @@
-use serde::{Deserialize, Deserializer};
+use std::{borrow::Cow, fmt};
+
+use serde::{
+    Deserialize, Deserializer,
+    de::{self, Visitor},
+};
 use serde_json::Value;
 
+/// Deserialize `value` as `T`, discarding top level fields that have an unexpected type instead of failing.
+///
+/// A field that fails to deserialize is first replaced with null, which suits `Option` fields, and then removed,
+/// which suits fields with a default. An error nested inside a top level field, such as in one element of an array,
+/// discards that whole field. If `T` still cannot be deserialized, for instance because a required field was
+/// discarded, the error is returned. Once it succeeds, `on_discarded` is called with the name of each field that was
+/// discarded.
+pub(crate) fn deserialize_lenient<T>(
+    mut value: Value,
+    mut on_discarded: impl FnMut(&str),
+) -> serde_json::Result<T>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    let mut discarded: Vec<String> = Vec::new();
+    loop {
+        let err = match serde_path_to_error::deserialize::<_, T>(&value) {
+            Ok(content) => {
+                for key in &discarded {
+                    on_discarded(key);
+                }
+                return Ok(content);
+            }
+            Err(err) => err,
+        };
+        let key = match err.path().iter().next() {
+            Some(serde_path_to_error::Segment::Map { key }) => key.clone(),
+            _ => return Err(err.into_inner()),
+        };
+        let Some(map) = value.as_object_mut() else {
+            return Err(err.into_inner());
+        };
+        match map.get_mut(&key) {
+            Some(field) if !field.is_null() => {
+                *field = Value::Null;
+                discarded.push(key);
+            }
+            Some(_) => {
+                map.remove(&key);
+            }
+            None => return Err(err.into_inner()),
+        }
+    }
+}
+
 pub(crate) fn deserialize_time<'de, D>(deserializer: D) -> Result<i64, D::Error>
 where
     D: Deserializer<'de>,
@@ -79,3 +129,83 @@ where
         _ => Err(serde::de::Error::custom("invalid timestamp value")),
     }
 }
+
+/// Deserialize an optional string, borrowing from the input unless it contains escape sequences.
+///
+/// `Option<Cow<str>>` fields always deserialize to an owned string without this.
+pub(crate) fn deserialize_optional_cow_str<'de, D>(
+    deserializer: D,
+) -> Result<Option<Cow<'de, str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct CowStrVisitor;
+
+    impl<'de> Visitor<'de> for CowStrVisitor {
+        type Value = Option<Cow<'de, str>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a string or null")
+        }
+
+        fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<Self::Value, E> {
+            Ok(Some(Cow::Borrowed(value)))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            Ok(Some(Cow::Owned(value.to_string())))
+        }
+
+        fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+            Ok(Some(Cow::Owned(value)))
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_str(self)
+        }
+    }
+
+    deserializer.deserialize_option(CowStrVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reddit_types::comment::Comment;
+
+    #[test]
+    fn test_deserialize_lenient() {
+        let comments = include_str!("../test_data/test_comments.json");
+        let mut comment: Value =
+            serde_json::from_str(comments.lines().next().unwrap()).expect("json");
+        comment["ups"] = "many".into();
+        comment["stickied"] = 1.into();
+        assert!(serde_json::from_value::<Comment>(comment.clone()).is_err());
+
+        let mut discarded: Vec<String> = Vec::new();
+        let lenient: Comment =
+            deserialize_lenient(comment.clone(), |key| discarded.push(key.to_string()))
+                .expect("lenient deserialization");
+        discarded.sort();
+        assert_eq!(discarded, vec!["stickied", "ups"]);
+        assert_eq!(lenient.ups, None);
+        assert!(!lenient.stickied);
+
+        // Fields discarded from an item that is rejected anyway are not reported
+        comment["body"] = 1.into();
+        let mut discarded = 0;
+        assert!(deserialize_lenient::<Comment>(comment, |_| discarded += 1).is_err());
+        assert_eq!(discarded, 0);
+    }
+}
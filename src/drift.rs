@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use log::info;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Statistics on how the JSON in the dumps differs from what `reddit_types` models.
+#[derive(Debug, Default)]
+pub struct SchemaDrift {
+    /// Keys present in the input but ignored during deserialization
+    unknown_keys: BTreeMap<String, u64>,
+    /// The JSON types seen for each top level key
+    field_types: BTreeMap<String, BTreeMap<&'static str, u64>>,
+    /// Fields set to null by lenient deserialization because their value had an unexpected type
+    discarded_fields: BTreeMap<String, u64>,
+    /// Enum values not known to the importer, keyed by field and value
+    unknown_variants: BTreeMap<(String, String), u64>,
+}
+
+impl SchemaDrift {
+    pub fn record_types(&mut self, value: &Value) {
+        if let Value::Object(map) = value {
+            for (key, field) in map {
+                *self
+                    .field_types
+                    .entry(key.clone())
+                    .or_default()
+                    .entry(json_type(field))
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    pub fn record_unknown_key(&mut self, key: String) {
+        *self.unknown_keys.entry(key).or_default() += 1;
+    }
+
+    pub fn record_discarded_field(&mut self, key: &str) {
+        *self.discarded_fields.entry(key.to_string()).or_default() += 1;
+    }
+
+    pub fn record_unknown_variant(&mut self, field: &str, value: &str) {
+        *self
+            .unknown_variants
+            .entry((field.to_string(), value.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Deserialize `value`, recording its field types and any keys `T` does not model.
+    pub fn deserialize<T>(&mut self, value: &Value) -> serde_json::Result<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        self.record_types(value);
+        serde_ignored::deserialize(value, |path| self.record_unknown_key(path.to_string()))
+    }
+
+    pub fn merge(&mut self, other: SchemaDrift) {
+        for (key, count) in other.unknown_keys {
+            *self.unknown_keys.entry(key).or_default() += count;
+        }
+        for (key, types) in other.field_types {
+            let entry = self.field_types.entry(key).or_default();
+            for (ty, count) in types {
+                *entry.entry(ty).or_default() += count;
+            }
+        }
+        for (key, count) in other.discarded_fields {
+            *self.discarded_fields.entry(key).or_default() += count;
+        }
+        for (variant, count) in other.unknown_variants {
+            *self.unknown_variants.entry(variant).or_default() += count;
+        }
+    }
+
+    pub fn field_types(&self) -> &BTreeMap<String, BTreeMap<&'static str, u64>> {
+        &self.field_types
+    }
+
+    /// Keys which held more than one kind of value, ignoring nulls.
+    pub fn mixed_type_fields(&self) -> impl Iterator<Item = (&str, &BTreeMap<&'static str, u64>)> {
+        self.field_types
+            .iter()
+            .filter(|(_, types)| types.keys().filter(|ty| **ty != "null").count() > 1)
+            .map(|(key, types)| (key.as_str(), types))
+    }
+
+    pub fn log_report(&self, content_type: &str) {
+        for (key, count) in &self.unknown_keys {
+            info!(content_type, key, count; "Unmodelled JSON key");
+        }
+        for (key, types) in self.mixed_type_fields() {
+            info!(content_type, key, types:? = types; "JSON key with multiple value types");
+        }
+        for (key, count) in &self.discarded_fields {
+            info!(content_type, key, count; "Field discarded due to unexpected type");
+        }
+        for ((field, value), count) in &self.unknown_variants {
+            info!(content_type, field, value, count; "Unknown enum value");
+        }
+    }
+}
+
+pub fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(number) if number.is_f64() => "float",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reddit_types::comment::Comment;
+
+    #[test]
+    fn test_comment_drift() -> anyhow::Result<()> {
+        let comments = include_str!("../test_data/test_comments.json");
+        let mut drift = SchemaDrift::default();
+        for line in comments.lines() {
+            let value: Value = serde_json::from_str(line)?;
+            let _comment: Comment = drift.deserialize(&value)?;
+        }
+
+        assert!(drift.unknown_keys.contains_key("mod_reports"));
+        assert!(!drift.unknown_keys.contains_key("body"));
+        assert!(!drift.mixed_type_fields().any(|(key, _)| key == "edited"));
+
+        drift.record_types(&serde_json::json!({"edited": 1712016633, "score": null}));
+        assert!(drift.mixed_type_fields().any(|(key, _)| key == "edited"));
+        assert!(!drift.mixed_type_fields().any(|(key, _)| key == "score"));
+
+        let mut merged = SchemaDrift::default();
+        merged.merge(drift);
+        assert!(merged.unknown_keys.contains_key("mod_reports"));
+        Ok(())
+    }
+}
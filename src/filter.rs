@@ -1,7 +1,8 @@
-use crate::Cli;
+use crate::{Cli, reddit_types::deletion::DeletionState};
 use ahash::HashSet;
 use chrono::NaiveDateTime;
 use log::warn;
+use serde::{Serialize, Serializer};
 
 const DATE_FORMAT: &str = "%Y-%m-%d-%H:%M:%S";
 
@@ -10,19 +11,59 @@ pub trait Filterable {
     fn author(&self) -> Option<&str>;
     fn subreddit(&self) -> Option<&str>;
     fn created(&self) -> i64;
+    /// Whether the content was deleted or removed, or `None` for content that cannot be deleted, such as subreddits
+    fn deletion_state(&self) -> Option<DeletionState>;
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Filter {
+    #[serde(serialize_with = "sorted")]
     users: HashSet<String>,
+    #[serde(serialize_with = "sorted")]
     subreddits: HashSet<String>,
     min_score: Option<i64>,
     max_score: Option<i64>,
     min_date: Option<i64>,
     max_date: Option<i64>,
+    /// Deletion states to keep, or every state if empty
+    #[serde(serialize_with = "sorted")]
+    deletion_states: HashSet<DeletionState>,
+    #[serde(serialize_with = "sorted")]
+    excluded_deletion_states: HashSet<DeletionState>,
+}
+
+/// Serialize a set in a stable order, so the same filter is always recorded the same way.
+fn sorted<T: Ord + Serialize, S: Serializer>(
+    set: &HashSet<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut items: Vec<_> = set.iter().collect();
+    items.sort();
+    serializer.collect_seq(items)
 }
 
 impl Filter {
+    /// Whether every item passes the filter.
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+            && self.subreddits.is_empty()
+            && self.min_score.is_none()
+            && self.max_score.is_none()
+            && self.min_date.is_none()
+            && self.max_date.is_none()
+            && self.deletion_states.is_empty()
+            && self.excluded_deletion_states.is_empty()
+    }
+
+    /// A filter with only the subreddit criteria of this one, for subreddit metadata which has no author, score or
+    /// meaningful date to filter on.
+    pub fn subreddit_filter(&self) -> Filter {
+        Filter {
+            subreddits: self.subreddits.clone(),
+            ..Default::default()
+        }
+    }
+
     pub fn filter<T: Filterable>(&self, content: &T) -> bool {
         match (self.min_score, content.score()) {
             (Some(min_score), Some(content_score)) if content_score < min_score => {
@@ -46,6 +87,13 @@ impl Filter {
             return false;
         }
 
+        if let Some(state) = content.deletion_state()
+            && ((!self.deletion_states.is_empty() && !self.deletion_states.contains(&state))
+                || self.excluded_deletion_states.contains(&state))
+        {
+            return false;
+        }
+
         if self.users.is_empty() && self.subreddits.is_empty() {
             return true;
         }
@@ -107,6 +155,22 @@ impl Filter {
             }
             _ => (),
         };
+        let deletion_states: HashSet<DeletionState> =
+            cli.deletion_state.iter().flatten().copied().collect();
+        let excluded_deletion_states: HashSet<DeletionState> = cli
+            .exclude_deletion_state
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        if deletion_states
+            .iter()
+            .any(|state| excluded_deletion_states.contains(state))
+        {
+            warn!(
+                "a deletion state is both selected and excluded, content in it will not be stored"
+            )
+        }
         Filter {
             users,
             subreddits,
@@ -114,6 +178,8 @@ impl Filter {
             max_score,
             min_date,
             max_date,
+            deletion_states,
+            excluded_deletion_states,
         }
     }
 }
@@ -137,6 +203,7 @@ mod tests {
         pub author: Option<String>,
         pub subreddit: Option<String>,
         pub created: i64,
+        pub deletion_state: Option<DeletionState>,
     }
 
     impl Filterable for ContentMock {
@@ -152,6 +219,9 @@ mod tests {
         fn created(&self) -> i64 {
             self.created
         }
+        fn deletion_state(&self) -> Option<DeletionState> {
+            self.deletion_state
+        }
     }
 
     fn set_subreddits(filter: &mut Filter, subreddits: &[&str]) {
@@ -254,6 +324,45 @@ mod tests {
         assert!(filter.filter(&content));
     }
 
+    #[test]
+    fn test_deletion_state() {
+        let mut filter = Filter::default();
+        let mut content = ContentMock {
+            deletion_state: Some(DeletionState::Removed),
+            ..Default::default()
+        };
+        assert!(filter.filter(&content));
+
+        filter.excluded_deletion_states = [DeletionState::Removed].into_iter().collect();
+        assert!(!filter.filter(&content));
+        content.deletion_state = Some(DeletionState::Present);
+        assert!(filter.filter(&content));
+
+        filter.deletion_states = [DeletionState::Deleted, DeletionState::Removed]
+            .into_iter()
+            .collect();
+        assert!(!filter.filter(&content));
+        content.deletion_state = Some(DeletionState::Deleted);
+        assert!(filter.filter(&content));
+
+        // Content without a deletion state, such as subreddits, is not filtered on it
+        content.deletion_state = None;
+        assert!(filter.filter(&content));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let mut filter = Filter::default();
+        assert!(filter.is_empty());
+
+        filter.max_date = Some(10);
+        assert!(!filter.is_empty());
+
+        filter.max_date = None;
+        set_subreddits(&mut filter, &["test"]);
+        assert!(!filter.is_empty());
+    }
+
     #[test]
     fn test_composite() {
         let mut filter = Filter {
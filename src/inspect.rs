@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, path::Path};
+
+use ahash::HashMap;
+use anyhow::Result;
+use fallible_streaming_iterator::FallibleStreamingIterator;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    decompress,
+    deser::deserialize_time,
+    drift::SchemaDrift,
+    reddit_types::{comment::Comment, submission::Submission},
+};
+
+/// Accumulates statistics about the lines of a dump file.
+#[derive(Debug, Default)]
+pub struct Inspector {
+    lines: u64,
+    invalid_json: u64,
+    min_created_utc: Option<i64>,
+    max_created_utc: Option<i64>,
+    subreddits: HashMap<String, u64>,
+    authors: HashMap<String, u64>,
+    schema: SchemaDrift,
+    comment_failures: u64,
+    submission_failures: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InspectReport {
+    pub lines: u64,
+    pub invalid_json: u64,
+    pub min_created_utc: Option<i64>,
+    pub max_created_utc: Option<i64>,
+    pub top_subreddits: Vec<(String, u64)>,
+    pub top_authors: Vec<(String, u64)>,
+    /// Every key seen at the top level of an item, with a count of each JSON type it held
+    pub keys: BTreeMap<String, BTreeMap<&'static str, u64>>,
+    pub comment_failures: u64,
+    pub submission_failures: u64,
+}
+
+#[derive(Deserialize)]
+struct CreatedUtc {
+    #[serde(deserialize_with = "deserialize_time")]
+    created_utc: i64,
+}
+
+impl Inspector {
+    pub fn inspect_line(&mut self, line: &str) {
+        // Remove leading and trailing non-json chars
+        let line = line.trim_matches(|ch| !(ch == '{' || ch == '}'));
+        if line.is_empty() {
+            return;
+        }
+        self.lines += 1;
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(_) => {
+                self.invalid_json += 1;
+                self.comment_failures += 1;
+                self.submission_failures += 1;
+                return;
+            }
+        };
+
+        self.schema.record_types(&value);
+        if let Value::Object(map) = &value {
+            if let Some(Value::String(subreddit)) = map.get("subreddit") {
+                *self.subreddits.entry(subreddit.clone()).or_default() += 1;
+            }
+            if let Some(Value::String(author)) = map.get("author") {
+                *self.authors.entry(author.clone()).or_default() += 1;
+            }
+        }
+
+        if let Ok(CreatedUtc { created_utc }) = CreatedUtc::deserialize(&value) {
+            self.min_created_utc = Some(
+                self.min_created_utc
+                    .map_or(created_utc, |min| min.min(created_utc)),
+            );
+            self.max_created_utc = Some(
+                self.max_created_utc
+                    .map_or(created_utc, |max| max.max(created_utc)),
+            );
+        }
+        if Comment::deserialize(&value).is_err() {
+            self.comment_failures += 1;
+        }
+        if Submission::deserialize(&value).is_err() {
+            self.submission_failures += 1;
+        }
+    }
+
+    pub fn report(self, top: usize) -> InspectReport {
+        InspectReport {
+            lines: self.lines,
+            invalid_json: self.invalid_json,
+            min_created_utc: self.min_created_utc,
+            max_created_utc: self.max_created_utc,
+            top_subreddits: top_counts(self.subreddits, top),
+            top_authors: top_counts(self.authors, top),
+            keys: self.schema.field_types().clone(),
+            comment_failures: self.comment_failures,
+            submission_failures: self.submission_failures,
+        }
+    }
+}
+
+/// Stream `filename` through the decompressor, inspecting at most `sample` lines if given.
+pub fn inspect_file(filename: &Path, sample: Option<u64>) -> Result<Inspector> {
+    let mut inspector = Inspector::default();
+    let mut lines = decompress::stream_lines(filename)?;
+    while let Some(line) = lines.next()? {
+        if Some(inspector.lines) == sample {
+            break;
+        }
+        inspector.inspect_line(line);
+    }
+    Ok(inspector)
+}
+
+fn top_counts(counts: HashMap<String, u64>, top: usize) -> Vec<(String, u64)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable_by(|(a_name, a_count), (b_name, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_name.cmp(b_name))
+    });
+    counts.truncate(top);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_comments() {
+        let comments = include_str!("../test_data/test_comments.json");
+        let mut inspector = Inspector::default();
+        for line in comments.lines() {
+            inspector.inspect_line(line);
+        }
+        inspector.inspect_line("{not json}");
+        let report = inspector.report(1);
+
+        assert_eq!(report.lines as usize, comments.lines().count() + 1);
+        assert_eq!(report.invalid_json, 1);
+        assert_eq!(report.comment_failures, 1);
+        assert_eq!(report.submission_failures, report.lines);
+        assert!(report.min_created_utc <= report.max_created_utc);
+        assert_eq!(report.top_subreddits.len(), 1);
+        assert_eq!(report.keys["body"].get("string"), Some(&(report.lines - 1)));
+    }
+}
@@ -0,0 +1,122 @@
+//! The JSON parsing backend used for input lines.
+//!
+//! `serde_json` is used by default. Building with the `simd-json` feature switches to `simd-json`, which produces the
+//! same types through the same `Deserialize` implementations.
+
+use serde::Deserialize;
+
+#[cfg(not(feature = "simd-json"))]
+pub type Error = serde_json::Error;
+#[cfg(feature = "simd-json")]
+pub type Error = simd_json::Error;
+
+/// Parses input lines, reusing its buffers between lines.
+#[derive(Default)]
+pub struct JsonParser {
+    /// simd-json parses in place, so each line is copied into this buffer first
+    #[cfg(feature = "simd-json")]
+    scratch: Vec<u8>,
+    #[cfg(feature = "simd-json")]
+    buffers: simd_json::Buffers,
+}
+
+impl JsonParser {
+    #[cfg(not(feature = "simd-json"))]
+    pub fn parse<'a, T>(&'a mut self, line: &'a str) -> Result<T, Error>
+    where
+        T: Deserialize<'a>,
+    {
+        serde_json::from_str(line)
+    }
+
+    #[cfg(feature = "simd-json")]
+    pub fn parse<'a, T>(&'a mut self, line: &'a str) -> Result<T, Error>
+    where
+        T: Deserialize<'a>,
+    {
+        self.scratch.clear();
+        self.scratch.extend_from_slice(line.as_bytes());
+        simd_json::serde::from_slice_with_buffers(&mut self.scratch, &mut self.buffers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        Filterable,
+        reddit_types::{comment::Comment, filter_view::FilterView, submission::Submission},
+    };
+
+    #[test]
+    fn test_parse() {
+        let mut parser = JsonParser::default();
+        let comments = include_str!("../test_data/test_comments.json");
+        for line in comments.lines() {
+            let comment: Comment = parser.parse(line).expect("deserialization");
+            let expected: Comment = serde_json::from_str(line).expect("deserialization");
+            assert_eq!(comment.id, expected.id);
+            assert_eq!(comment.body, expected.body);
+            let view: FilterView = parser.parse(line).expect("view deserialization");
+            assert_eq!(view.created(), expected.created_utc);
+        }
+        let submissions = include_str!("../test_data/test_submissions.json");
+        for line in submissions.lines() {
+            let submission: Submission = parser.parse(line).expect("deserialization");
+            let expected: Submission = serde_json::from_str(line).expect("deserialization");
+            assert_eq!(submission.id, expected.id);
+            assert_eq!(submission.selftext, expected.selftext);
+        }
+    }
+
+    fn throughput<T>(name: &str, lines: &[&str], mut parse: impl FnMut(&str) -> T) {
+        const ITERATIONS: usize = 2000;
+        let bytes: usize = lines.iter().map(|line| line.len()).sum();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            for line in lines {
+                std::hint::black_box(parse(line));
+            }
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        println!(
+            "{name}: {:.0} lines/s, {:.1} MB/s",
+            (lines.len() * ITERATIONS) as f64 / elapsed,
+            (bytes * ITERATIONS) as f64 / elapsed / 1e6
+        );
+    }
+
+    /// Compares serde_json with the configured backend on the files in `test_data`. Run with
+    /// `cargo test --release --features simd-json bench_parse -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_parse_throughput() {
+        let comments: Vec<_> = include_str!("../test_data/test_comments.json")
+            .lines()
+            .collect();
+        let submissions: Vec<_> = include_str!("../test_data/test_submissions.json")
+            .lines()
+            .collect();
+        let backend = if cfg!(feature = "simd-json") {
+            "simd-json"
+        } else {
+            "serde_json"
+        };
+        let mut parser = JsonParser::default();
+
+        throughput("serde_json comments", &comments, |line| {
+            serde_json::from_str::<Comment>(line).expect("deserialization")
+        });
+        throughput(&format!("{backend} comments"), &comments, |line| {
+            parser.parse::<Comment>(line).expect("deserialization")
+        });
+        throughput("serde_json submissions", &submissions, |line| {
+            serde_json::from_str::<Submission>(line).expect("deserialization")
+        });
+        throughput(&format!("{backend} submissions"), &submissions, |line| {
+            parser.parse::<Submission>(line).expect("deserialization")
+        });
+    }
+}
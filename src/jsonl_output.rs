@@ -0,0 +1,356 @@
+//! Writes imported items back out as compressed JSON Lines, so a filtered subset of a dump can be shared as a dump.
+//!
+//! Comments, submissions and subreddits are written to `comment.jsonl`, `submission.jsonl` and `subreddit.jsonl`
+//! in the output directory, with the extension of the compression. Files that already exist are appended to as a
+//! further zstd frame or gzip member, which decompressors read as a continuation of the file.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use rusqlite::types::ValueRef;
+use serde_json::{Map, Value};
+
+use crate::{
+    reddit_types::{RawJson, comment::Comment, submission::Submission, subreddit::Subreddit},
+    sqlite::{COMMENT_COLUMNS, Column, ColumnSelection, SUBMISSION_COLUMNS, value_ref},
+    storage::Storage,
+};
+
+/// How JSON Lines output is compressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JsonlCompression {
+    #[default]
+    Zstd,
+    Gzip,
+    /// Write plain text
+    None,
+}
+
+impl JsonlCompression {
+    fn extension(self) -> &'static str {
+        match self {
+            JsonlCompression::Zstd => "jsonl.zst",
+            JsonlCompression::Gzip => "jsonl.gz",
+            JsonlCompression::None => "jsonl",
+        }
+    }
+}
+
+/// What is written for each item.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JsonlContent {
+    /// The columns the Sqlite schema stores, as one object per line
+    #[default]
+    Fields,
+    /// The original line the item was read from
+    Raw,
+}
+
+/// Settings for writing JSON Lines.
+#[derive(Debug, Clone, Default)]
+pub struct JsonlOptions {
+    pub compression: JsonlCompression,
+    pub content: JsonlContent,
+    pub comment_columns: ColumnSelection,
+    pub submission_columns: ColumnSelection,
+}
+
+enum Encoder {
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    None(BufWriter<File>),
+}
+
+impl Encoder {
+    fn create(path: &Path, compression: JsonlCompression) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("unable to open {}", path.display()))?;
+        let writer = BufWriter::new(file);
+        Ok(match compression {
+            JsonlCompression::Zstd => {
+                Encoder::Zstd(zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?)
+            }
+            JsonlCompression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+            JsonlCompression::None => Encoder::None(writer),
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::Zstd(encoder) => encoder,
+            Encoder::Gzip(encoder) => encoder,
+            Encoder::None(writer) => writer,
+        }
+    }
+
+    /// Write the end of the compressed stream and flush it to the file.
+    fn finish(self) -> Result<()> {
+        let mut writer = match self {
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::None(writer) => writer,
+        };
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A JSON Lines file for one kind of item, opened when the first item is written.
+struct JsonlFile {
+    path: PathBuf,
+    compression: JsonlCompression,
+    encoder: Option<Encoder>,
+}
+
+impl JsonlFile {
+    fn new(directory: &Path, kind: &str, compression: JsonlCompression) -> Self {
+        JsonlFile {
+            path: directory.join(format!("{kind}.{}", compression.extension())),
+            compression,
+            encoder: None,
+        }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> Result<()> {
+        let encoder = match &mut self.encoder {
+            Some(encoder) => encoder,
+            None => {
+                if let Some(directory) = self.path.parent() {
+                    fs::create_dir_all(directory).with_context(|| {
+                        format!("unable to create directory {}", directory.display())
+                    })?;
+                }
+                self.encoder
+                    .insert(Encoder::create(&self.path, self.compression)?)
+            }
+        };
+        let writer = encoder.writer();
+        writer.write_all(line)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for JsonlFile {
+    fn drop(&mut self) {
+        // A compressed stream is truncated until its end is written
+        let _ = self.finish();
+    }
+}
+
+/// Writes comments, submissions and subreddits to JSON Lines files in an output directory.
+pub struct Jsonl {
+    content: JsonlContent,
+    comment_columns: Vec<&'static Column<Comment>>,
+    submission_columns: Vec<&'static Column<Submission>>,
+    comments: JsonlFile,
+    submissions: JsonlFile,
+    subreddits: JsonlFile,
+    line: Vec<u8>,
+}
+
+impl Jsonl {
+    pub fn new(directory: &Path, options: JsonlOptions) -> Result<Self> {
+        let compression = options.compression;
+        Ok(Jsonl {
+            content: options.content,
+            comment_columns: options.comment_columns.resolve(COMMENT_COLUMNS, &[], &[])?,
+            submission_columns: options
+                .submission_columns
+                .resolve(SUBMISSION_COLUMNS, &[], &[])?,
+            comments: JsonlFile::new(directory, "comment", compression),
+            submissions: JsonlFile::new(directory, "submission", compression),
+            subreddits: JsonlFile::new(directory, "subreddit", compression),
+            line: Vec::new(),
+        })
+    }
+}
+
+/// Encode the original line of an item, which must have been kept.
+fn raw_line(raw_json: Option<&RawJson>, line: &mut Vec<u8>) -> Result<()> {
+    match raw_json {
+        Some(RawJson::Plain(raw)) => line.extend_from_slice(raw.as_bytes()),
+        Some(RawJson::Zstd(compressed)) => line.extend(zstd::decode_all(compressed.as_slice())?),
+        None => return Err(anyhow!("the original line of an item was not kept")),
+    }
+    Ok(())
+}
+
+/// Encode the columns of an item as a JSON object.
+fn fields_line<T>(columns: &[&'static Column<T>], item: &T, line: &mut Vec<u8>) -> Result<()> {
+    let mut object = Map::with_capacity(columns.len());
+    for column in columns {
+        let output = (column.value)(item)?;
+        let value = match value_ref(&output) {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(value) if column.definition.starts_with("BOOLEAN") => {
+                Value::Bool(value != 0)
+            }
+            ValueRef::Integer(value) => value.into(),
+            ValueRef::Real(value) => value.into(),
+            ValueRef::Text(value) => std::str::from_utf8(value)?.into(),
+            // Blob columns hold flair template ids
+            ValueRef::Blob(value) => uuid::Uuid::from_slice(value)?.to_string().into(),
+        };
+        object.insert(column.name.to_string(), value);
+    }
+    serde_json::to_writer(line, &object)?;
+    Ok(())
+}
+
+impl Storage for Jsonl {
+    fn insert_comment(&mut self, comment: Comment) -> Result<usize> {
+        self.line.clear();
+        match self.content {
+            JsonlContent::Fields => fields_line(&self.comment_columns, &comment, &mut self.line)?,
+            JsonlContent::Raw => raw_line(comment.raw_json.as_ref(), &mut self.line)?,
+        }
+        self.comments.write_line(&self.line)?;
+        Ok(0)
+    }
+
+    fn insert_submission(&mut self, submission: Submission) -> Result<usize> {
+        self.line.clear();
+        match self.content {
+            JsonlContent::Fields => {
+                fields_line(&self.submission_columns, &submission, &mut self.line)?
+            }
+            JsonlContent::Raw => raw_line(submission.raw_json.as_ref(), &mut self.line)?,
+        }
+        self.submissions.write_line(&self.line)?;
+        Ok(0)
+    }
+
+    fn insert_subreddit(&mut self, subreddit: Subreddit) -> Result<usize> {
+        if self.content != JsonlContent::Raw {
+            return Err(anyhow!(
+                "subreddit metadata can only be written as the original lines"
+            ));
+        }
+        self.line.clear();
+        raw_line(subreddit.raw_json.as_ref(), &mut self.line)?;
+        self.subreddits.write_line(&self.line)?;
+        Ok(0)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.comments.finish()?;
+        self.submissions.finish()?;
+        self.subreddits.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decompress::stream_lines, reddit_types::RawJsonFormat};
+    use fallible_streaming_iterator::FallibleStreamingIterator;
+
+    fn read_lines(path: &Path) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        let mut stream = stream_lines(path)?;
+        while let Some(line) = stream.next()? {
+            lines.push(line.trim_end().to_string());
+        }
+        Ok(lines)
+    }
+
+    #[test]
+    fn test_jsonl() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let directory = temp.path();
+        let lines: Vec<&str> = include_str!("../test_data/test_comments.json")
+            .lines()
+            .take(3)
+            .collect();
+        let comments = |format: RawJsonFormat| -> Result<Vec<Comment>> {
+            lines
+                .iter()
+                .map(|line| {
+                    let mut comment: Comment = serde_json::from_str(line)?;
+                    comment.raw_json = Some(format.encode(line)?);
+                    Ok(comment)
+                })
+                .collect()
+        };
+
+        let mut jsonl = Jsonl::new(
+            &directory.join("raw"),
+            JsonlOptions {
+                content: JsonlContent::Raw,
+                ..Default::default()
+            },
+        )?;
+        for comment in comments(RawJsonFormat::Zstd)? {
+            jsonl.insert_comment(comment)?;
+        }
+        jsonl.finish()?;
+        // Appending adds to the same file
+        let mut jsonl = Jsonl::new(
+            &directory.join("raw"),
+            JsonlOptions {
+                content: JsonlContent::Raw,
+                ..Default::default()
+            },
+        )?;
+        jsonl.insert_comment(comments(RawJsonFormat::Plain)?.remove(0))?;
+        jsonl.finish()?;
+        let written = read_lines(&directory.join("raw/comment.jsonl.zst"))?;
+        assert_eq!(written.len(), 4);
+        assert_eq!(written[..3], lines[..]);
+        assert_eq!(written[3], lines[0]);
+        assert!(!directory.join("raw/submission.jsonl.zst").exists());
+
+        let mut jsonl = Jsonl::new(
+            &directory.join("fields"),
+            JsonlOptions {
+                compression: JsonlCompression::Gzip,
+                comment_columns: ColumnSelection {
+                    columns: Some(vec!["body".to_string(), "stickied".to_string()]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )?;
+        let comments = comments(RawJsonFormat::Plain)?;
+        for comment in comments.iter().cloned() {
+            jsonl.insert_comment(comment)?;
+        }
+        assert!(
+            jsonl
+                .insert_subreddit(serde_json::from_str(
+                    include_str!("../test_data/test_subreddits.json")
+                        .lines()
+                        .next()
+                        .unwrap_or_default()
+                )?)
+                .is_err()
+        );
+        jsonl.finish()?;
+        let written = read_lines(&directory.join("fields/comment.jsonl.gz"))?;
+        let first: Value = serde_json::from_str(&written[0])?;
+        assert_eq!(first["reddit_id"], comments[0].id.as_str());
+        assert_eq!(first["body"], comments[0].body.as_str());
+        assert_eq!(first["stickied"], comments[0].stickied);
+        assert!(first.get("score").is_none());
+        Ok(())
+    }
+}
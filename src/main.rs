@@ -14,7 +14,7 @@ use std::{
     fs,
     path::{Path, PathBuf},
     sync::{
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
         atomic::{AtomicUsize, Ordering},
         mpsc,
     },
@@ -22,22 +22,46 @@ use std::{
 };
 
 use anyhow::Result;
-use clap::{CommandFactory, Parser, error::ErrorKind};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
 use fallible_streaming_iterator::FallibleStreamingIterator;
 use log::{error, info, warn};
 use serde::Deserialize;
-use sqlite::Sqlite;
+use serde_json::Value;
+use sqlite::{
+    ColumnProfile, ColumnSelection, ConflictPolicy, FtsBuild, FtsIndexOptions, FtsTokenizer,
+    Sqlite, SqliteOptions,
+};
 use structured_logger::{Builder as LoggerBuilder, json::new_writer};
 
 use crate::{
+    checksum::{Manifest, MismatchPolicy, VerificationStatus, VerifiedFile},
+    drift::SchemaDrift,
     filter::{Filter, Filterable, date_format_validator},
-    reddit_types::{comment::Comment, submission::Submission},
-    storage::{Storable, Storage},
+    json::JsonParser,
+    jsonl_output::{Jsonl, JsonlCompression, JsonlContent, JsonlOptions},
+    parquet_output::{
+        DEFAULT_ROW_GROUP_SIZE, DEFAULT_ROW_GROUPS_PER_FILE, Parquet, ParquetOptions,
+    },
+    partition::{DEFAULT_MAX_OPEN_PARTITIONS, DEFAULT_PARTITION_BUFFER_ROWS, Partitioned},
+    provenance::{InputFile, Provenance},
+    reddit_types::{
+        RawJsonFormat, UnknownVariants, comment::Comment, deletion::DeletionState,
+        filter_view::FilterView, submission::Submission, subreddit::Subreddit,
+    },
+    storage::{OutputFormat, Storable, Storage},
 };
 
+mod checksum;
 mod decompress;
 mod deser;
+mod drift;
 mod filter;
+mod inspect;
+mod json;
+mod jsonl_output;
+mod parquet_output;
+mod partition;
+mod provenance;
 pub(crate) mod reddit_types;
 mod sqlite;
 mod storage;
@@ -49,9 +73,50 @@ mod storage;
 #[command(
     about = "Import data from pushshift dump into a Sqlite database. Currently limited to comment data only. Multiple filters can be applied, and if any of the filter criteria match, the comment is included. If no filters are supplied, all comments match; ie the whole dataset will be added to the sqlite file."
 )]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Path for for output Sqlite database.
-    sqlite_outfile: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path for for output Sqlite database, or the output directory with --output-format parquet.
+    #[arg(required = true)]
+    output: Option<PathBuf>,
+
+    /// What to write imported items to
+    #[arg(long = "output-format", value_enum, default_value_t = OutputFormat::Sqlite)]
+    output_format: OutputFormat,
+
+    /// Rows in each Parquet row group. Larger row groups compress better, but every row of one is held in memory.
+    #[arg(long = "row-group-size", default_value_t = DEFAULT_ROW_GROUP_SIZE)]
+    row_group_size: usize,
+
+    /// Row groups in each Parquet file, after which a new file is started
+    #[arg(long = "row-groups-per-file", default_value_t = DEFAULT_ROW_GROUPS_PER_FILE)]
+    row_groups_per_file: usize,
+
+    /// Lay out Parquet output as subreddit=X/year=Y/month=M partitions, so query engines can skip partitions a query
+    /// excludes
+    #[arg(long)]
+    partition: bool,
+
+    /// How to compress JSON Lines output
+    #[arg(long = "jsonl-compression", value_enum, default_value_t = JsonlCompression::Zstd)]
+    jsonl_compression: JsonlCompression,
+
+    /// Whether JSON Lines output holds the columns of the Sqlite schema, or the original lines of the dump. Only the
+    /// original lines can be written for subreddit metadata.
+    #[arg(long = "jsonl-content", value_enum, default_value_t = JsonlContent::Fields)]
+    jsonl_content: JsonlContent,
+
+    /// The most partitions to keep open at once. When another is needed the least recently used one is closed, so a
+    /// higher limit writes fewer, larger files, at the cost of buffering a row group for each open partition.
+    #[arg(long = "max-open-partitions", default_value_t = DEFAULT_MAX_OPEN_PARTITIONS, requires = "partition")]
+    max_open_partitions: usize,
+
+    /// The most rows to hold in memory across all open partitions. Row groups of partitioned output are capped at this
+    /// divided by --max-open-partitions rows.
+    #[arg(long = "partition-buffer-rows", default_value_t = DEFAULT_PARTITION_BUFFER_ROWS, requires = "partition")]
+    partition_buffer_rows: usize,
 
     /// Directory where compressed json files containing comments are located
     #[arg(long)]
@@ -61,6 +126,10 @@ struct Cli {
     #[arg(long)]
     submissions: Option<PathBuf>,
 
+    /// Directory where compressed json files containing subreddit metadata are located
+    #[arg(long)]
+    subreddits: Option<PathBuf>,
+
     /// File containing filter configuration
     #[arg(long = "filter-config")]
     filter_config: Option<String>,
@@ -89,6 +158,14 @@ struct Cli {
     #[arg(long = "max-datetime", value_parser = date_format_validator)]
     max_datetime: Option<String>,
 
+    /// Only include content in one of these deletion states
+    #[arg(long = "deletion-state", value_enum, num_args = 1..)]
+    deletion_state: Option<Vec<DeletionState>>,
+
+    /// Exclude content in these deletion states, such as removed-by-moderator
+    #[arg(long = "exclude-deletion-state", value_enum, num_args = 1..)]
+    exclude_deletion_state: Option<Vec<DeletionState>>,
+
     /// Store some database structures in memory, improving performance at the const of durability. Errors will cause database corruption. This flag is used for testing.
     #[arg(long = "unsafe-mode")]
     unsafe_mode: bool,
@@ -96,6 +173,192 @@ struct Cli {
     /// Enable full text search features. Creates a larger database and takes longer to run.
     #[arg(long = "enable-fts")]
     enable_fts: bool,
+
+    /// When to write the full text search indexes. post-load indexes everything in one pass at the end of the import,
+    /// which is much faster, but leaves the indexes incomplete if the import is interrupted until `fts rebuild` is run.
+    #[arg(long = "fts-build", value_enum, default_value_t = FtsBuild::Triggers, requires = "enable_fts")]
+    fts_build: FtsBuild,
+
+    #[command(flatten)]
+    fts_index: FtsIndexArgs,
+
+    /// SHA-256 checksum manifest to verify input files against before importing
+    #[arg(long)]
+    checksums: Option<PathBuf>,
+
+    /// What to do when an input file is missing from, or does not match, the checksum manifest
+    #[arg(long = "checksum-mismatch", value_enum, default_value_t = MismatchPolicy::Refuse, requires = "checksums")]
+    checksum_mismatch: MismatchPolicy,
+
+    /// Record the SHA-256 hashes of input files in the import metadata, as well as their sizes. Hashing reads every
+    /// input file an extra time. Input files are hashed anyway when --checksums is given.
+    #[arg(long = "hash-inputs")]
+    hash_inputs: bool,
+
+    /// Report JSON keys that are not imported, and keys whose values have more than one type. Slows down the import.
+    #[arg(long = "drift-report")]
+    drift_report: bool,
+
+    /// Which optional columns to store. The full profile adds comment fields such as controversiality, score_hidden,
+    /// body_sha1, author_fullname, updated_on and author_flair_css_class.
+    #[arg(long, value_enum, default_value_t = ColumnProfile::Lean)]
+    columns: ColumnProfile,
+
+    /// Store only these comment columns, separated by commas, instead of those in the --columns profile. The id and
+    /// created_utc columns, and any the comment table already has, are always stored.
+    #[arg(long = "comment-columns", value_delimiter = ',')]
+    comment_columns: Option<Vec<String>>,
+
+    /// Store only these submission columns, separated by commas, instead of those in the --columns profile. The id
+    /// and created_utc columns, and any the submission table already has, are always stored.
+    #[arg(long = "submission-columns", value_delimiter = ',')]
+    submission_columns: Option<Vec<String>>,
+
+    /// What to do with items that are already in the database, such as when importing a newer re-scrape. keep-latest
+    /// compares retrieved_on, and merge-non-null only overwrites fields the incoming item has.
+    #[arg(long = "on-conflict", value_enum, default_value_t = ConflictPolicy::KeepFirst)]
+    on_conflict: ConflictPolicy,
+
+    /// Record versions of comments and submissions that differ from the latest version seen in comment_revision and
+    /// submission_revision, with the file they were read from
+    #[arg(long)]
+    revisions: bool,
+
+    /// Store each author and subreddit name once, in the author and subreddit tables, with comments and submissions
+    /// referring to them by id. Views named comment and submission expose the usual columns.
+    #[arg(long, conflicts_with = "enable_fts")]
+    normalize: bool,
+
+    /// Keep the original JSON line of every imported item in a raw_json column
+    #[arg(long = "raw-json", value_enum)]
+    raw_json: Option<RawJsonFormat>,
+
+    /// Keep items with unknown enum values or unexpectedly typed optional fields, storing unknown values verbatim and
+    /// mistyped fields as NULL. Affected fields are counted in the drift report.
+    #[arg(long)]
+    lenient: bool,
+}
+
+/// Options controlling how input lines are turned into content.
+#[derive(Debug, Clone, Copy, Default)]
+struct ImportOptions {
+    drift_report: bool,
+    raw_json: Option<RawJsonFormat>,
+    revisions: bool,
+    lenient: bool,
+}
+
+impl ImportOptions {
+    fn from_cli(cli: &Cli) -> Self {
+        // Re-exporting the original lines needs them kept
+        let raw_jsonl =
+            cli.output_format == OutputFormat::Jsonl && cli.jsonl_content == JsonlContent::Raw;
+        ImportOptions {
+            drift_report: cli.drift_report,
+            raw_json: if raw_jsonl {
+                Some(RawJsonFormat::Plain)
+            } else {
+                cli.raw_json
+            },
+            revisions: cli.revisions,
+            lenient: cli.lenient,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Verify input files against a SHA-256 checksum manifest without importing them
+    Verify(VerifyArgs),
+    /// Report what a compressed json file contains without importing it
+    Inspect(InspectArgs),
+    /// Manage the full text search indexes of a database
+    Fts(FtsArgs),
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// Checksum manifest, in `sha256sum` or BSD format
+    #[arg(long)]
+    manifest: PathBuf,
+
+    /// Directory where compressed json files containing comments are located
+    #[arg(long)]
+    comments: Option<PathBuf>,
+
+    /// Directory where compressed json files containing submissions are located
+    #[arg(long)]
+    submissions: Option<PathBuf>,
+
+    /// Directory where compressed json files containing subreddit metadata are located
+    #[arg(long)]
+    subreddits: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    /// Compressed json file containing comments or submissions
+    file: PathBuf,
+
+    /// Only inspect the first N lines of the file
+    #[arg(long)]
+    sample: Option<u64>,
+
+    /// Number of subreddits and authors to list
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+}
+
+#[derive(Args)]
+struct FtsArgs {
+    #[command(subcommand)]
+    command: FtsCommand,
+}
+
+#[derive(Subcommand)]
+enum FtsCommand {
+    /// Build comment_fts and submission_fts from the comment and submission tables, replacing any existing index. Use
+    /// this to index a database imported without --enable-fts, to change the tokenizer or indexed columns, or to
+    /// repair an index. Settings that are not given are kept from the existing index.
+    Rebuild {
+        /// Sqlite database to index
+        database: PathBuf,
+
+        #[command(flatten)]
+        index: FtsIndexArgs,
+    },
+}
+
+#[derive(Args)]
+struct FtsIndexArgs {
+    /// Tokenizer of the full text search indexes. porter matches different forms of English words, and trigram
+    /// supports substring search at the cost of a much larger index. [default: unicode61]
+    #[arg(long = "fts-tokenizer", value_enum)]
+    fts_tokenizer: Option<FtsTokenizer>,
+
+    /// Whether the tokenizer removes diacritics, so that "cafe" matches "café": 0 keeps them, 1 or 2 remove them. 2
+    /// is the most thorough, but is not supported by trigram.
+    #[arg(long = "fts-remove-diacritics", value_parser = clap::value_parser!(u8).range(0..=2))]
+    fts_remove_diacritics: Option<u8>,
+
+    /// Comment columns to index for full text search, separated by commas [default: body]
+    #[arg(long = "fts-comment-columns", value_delimiter = ',')]
+    fts_comment_columns: Option<Vec<String>>,
+
+    /// Submission columns to index for full text search, separated by commas [default: title,selftext]
+    #[arg(long = "fts-submission-columns", value_delimiter = ',')]
+    fts_submission_columns: Option<Vec<String>>,
+}
+
+impl FtsIndexArgs {
+    fn options(&self) -> FtsIndexOptions {
+        FtsIndexOptions {
+            tokenizer: self.fts_tokenizer,
+            remove_diacritics: self.fts_remove_diacritics,
+            comment_columns: self.fts_comment_columns.clone(),
+            submission_columns: self.fts_submission_columns.clone(),
+        }
+    }
 }
 
 fn main() {
@@ -104,53 +367,343 @@ fn main() {
         .init();
 
     let cli = Cli::parse();
-    let mut command = Cli::command();
-    if Some(false)
-        == cli
-            .comments
-            .as_ref()
-            .map(|comments_path| comments_path.exists() && comments_path.is_dir())
-    {
-        command.error(ErrorKind::ValueValidation, "comments must be a directory and exist").exit();
+    match &cli.command {
+        Some(Command::Verify(args)) => verify(args),
+        Some(Command::Inspect(args)) => inspect(args),
+        Some(Command::Fts(args)) => fts(args),
+        None => import(&cli),
     }
-    if Some(false)
-        == cli
-        .submissions
-        .as_ref()
-        .map(|submissions_path| submissions_path.exists() && submissions_path.is_dir())
-    {
-        command.error(ErrorKind::ValueValidation, "submission must be a directory and exist").exit();
+}
+
+fn import(cli: &Cli) {
+    let started_utc = chrono::Utc::now().timestamp();
+    validate_output_format(cli);
+    validate_input_dir(cli.comments.as_deref(), "comments");
+    validate_input_dir(cli.submissions.as_deref(), "submission");
+    validate_input_dir(cli.subreddits.as_deref(), "subreddits");
+    let comment_files = cli.comments.as_deref().map(get_file_list);
+    let submission_files = cli.submissions.as_deref().map(get_file_list);
+    let subreddit_files = cli.subreddits.as_deref().map(get_file_list);
+
+    let files: Vec<PathBuf> = comment_files
+        .iter()
+        .chain(submission_files.iter())
+        .chain(subreddit_files.iter())
+        .flatten()
+        .cloned()
+        .collect();
+    let verified_files = cli.checksums.as_ref().map(|manifest_path| {
+        let manifest = Manifest::from_file(manifest_path).expect("Error reading checksum manifest");
+        info!("Verifying {} input files", files.len());
+        let verified =
+            checksum::verify_files(&manifest, &files).expect("Error hashing input files");
+        let failures = log_verification(&verified);
+        if failures > 0 {
+            if cli.checksum_mismatch == MismatchPolicy::Refuse {
+                error!(
+                    "{} input files failed checksum verification. Refusing to import",
+                    failures
+                );
+                std::process::exit(1);
+            }
+            warn!(
+                "{} input files failed checksum verification. Importing anyway",
+                failures
+            );
+        }
+        verified
+    });
+    let input_files: Vec<InputFile> = match &verified_files {
+        Some(verified) => verified.iter().map(InputFile::from_verified).collect(),
+        None if cli.hash_inputs => {
+            info!("Hashing {} input files", files.len());
+            checksum::verify_files(&Manifest::default(), &files)
+                .expect("Error hashing input files")
+                .iter()
+                .map(InputFile::from_verified)
+                .collect()
+        }
+        None => files
+            .iter()
+            .map(|file| InputFile::unhashed(file))
+            .collect::<Result<_>>()
+            .expect("Error reading input file size"),
+    };
+
+    let output = cli.output.as_ref().expect("output is required");
+    let comment_columns = ColumnSelection {
+        profile: cli.columns,
+        columns: cli.comment_columns.clone(),
+    };
+    let submission_columns = ColumnSelection {
+        profile: cli.columns,
+        columns: cli.submission_columns.clone(),
+    };
+    let filter: Arc<Filter> = Arc::new(Filter::from_cli(cli));
+    let mut provenance = Provenance::new((*filter).clone(), input_files, started_utc);
+    let options = ImportOptions::from_cli(cli);
+    let lists = FileLists {
+        comments: comment_files,
+        submissions: submission_files,
+        subreddits: subreddit_files,
+    };
+    match cli.output_format {
+        OutputFormat::Sqlite => {
+            let mut sqlite = Sqlite::new(
+                output,
+                SqliteOptions {
+                    unsafe_pragma: cli.unsafe_mode,
+                    fts: cli.enable_fts,
+                    fts_build: cli.fts_build,
+                    fts_index: cli.fts_index.options(),
+                    raw_json: cli.raw_json.is_some(),
+                    normalized: cli.normalize,
+                    comment_columns,
+                    submission_columns,
+                    on_conflict: cli.on_conflict,
+                    revisions: cli.revisions,
+                },
+            )
+            .expect("Error setting up sqlite DB");
+            for file in verified_files.iter().flatten() {
+                sqlite
+                    .record_input_file(file)
+                    .expect("Error recording input file checksum");
+            }
+            load(lists, filter, options, &mut sqlite, &mut provenance);
+            sqlite
+                .record_provenance(&provenance)
+                .expect("Error recording import metadata");
+        }
+        OutputFormat::Parquet => {
+            let parquet_options = ParquetOptions {
+                row_group_size: cli.row_group_size,
+                row_groups_per_file: cli.row_groups_per_file,
+                raw_json: cli.raw_json.is_some(),
+                comment_columns,
+                submission_columns,
+            };
+            if cli.partition {
+                let parquet_options =
+                    parquet_options.partitioned(cli.max_open_partitions, cli.partition_buffer_rows);
+                let mut partitioned = Partitioned::new(
+                    output,
+                    cli.max_open_partitions,
+                    |kind: &str, directory: &Path| {
+                        Parquet::partition(kind, directory, &parquet_options)
+                    },
+                )
+                .expect("Error setting up partitioned output");
+                load(lists, filter, options, &mut partitioned, &mut provenance);
+            } else {
+                let mut parquet =
+                    Parquet::new(output, parquet_options).expect("Error setting up Parquet output");
+                load(lists, filter, options, &mut parquet, &mut provenance);
+            }
+            provenance
+                .append_to_directory(output)
+                .expect("Error recording import metadata");
+        }
+        OutputFormat::Jsonl => {
+            let mut jsonl = Jsonl::new(
+                output,
+                JsonlOptions {
+                    compression: cli.jsonl_compression,
+                    content: cli.jsonl_content,
+                    comment_columns,
+                    submission_columns,
+                },
+            )
+            .expect("Error setting up JSON Lines output");
+            load(lists, filter, options, &mut jsonl, &mut provenance);
+            provenance
+                .append_to_directory(output)
+                .expect("Error recording import metadata");
+        }
     }
-    let mut sqlite = Sqlite::new(&cli.sqlite_outfile, cli.unsafe_mode, cli.enable_fts)
-        .expect("Error setting up sqlite DB");
-    let filter: Arc<Filter> = Arc::new(Filter::from_cli(&cli));
-    if let Some(comments_dir) = &cli.comments {
-        let file_list = get_file_list(comments_dir);
+}
+
+/// The input files of each kind, if any.
+struct FileLists {
+    comments: Option<Vec<PathBuf>>,
+    submissions: Option<Vec<PathBuf>>,
+    subreddits: Option<Vec<PathBuf>>,
+}
+
+/// Import every input file into `storage`, counting the items of each kind in `provenance`.
+fn load<T: Storage>(
+    lists: FileLists,
+    filter: Arc<Filter>,
+    options: ImportOptions,
+    storage: &mut T,
+    provenance: &mut Provenance,
+) {
+    if let Some(file_list) = lists.subreddits {
+        info!("Processing subreddits");
+        let subreddit_filter = Arc::new(filter.subreddit_filter());
+        let (count, drift) = process::<_, Subreddit>(file_list, subreddit_filter, options, storage);
+        drift.log_report("subreddit");
+        provenance.item_counts.insert("subreddit", count);
+    }
+    if let Some(file_list) = lists.comments {
         info!("Processing comments");
-        process::<_, Comment>(file_list, filter.clone(), &mut sqlite);
+        let (count, drift) = process::<_, Comment>(file_list, filter.clone(), options, storage);
+        drift.log_report("comment");
+        provenance.item_counts.insert("comment", count);
     }
-    if let Some(submissions_dir) = &cli.submissions {
-        let file_list = get_file_list(submissions_dir);
+    if let Some(file_list) = lists.submissions {
         info!("Processing submissions");
-        process::<_, Submission>(file_list, filter, &mut sqlite);
+        let (count, drift) = process::<_, Submission>(file_list, filter, options, storage);
+        drift.log_report("submission");
+        provenance.item_counts.insert("submission", count);
+    }
+    storage.finish().expect("Error finishing import");
+    provenance.finished_utc = chrono::Utc::now().timestamp();
+}
+
+/// Exit with an error if an option is used with an output format that does not support it.
+fn validate_output_format(cli: &Cli) {
+    let sqlite = cli.output_format == OutputFormat::Sqlite;
+    let raw_jsonl =
+        cli.output_format == OutputFormat::Jsonl && cli.jsonl_content == JsonlContent::Raw;
+    let options = [
+        (
+            "--subreddits",
+            cli.subreddits.is_some(),
+            sqlite || raw_jsonl,
+        ),
+        ("--enable-fts", cli.enable_fts, sqlite),
+        ("--normalize", cli.normalize, sqlite),
+        ("--revisions", cli.revisions, sqlite),
+        (
+            "--on-conflict",
+            cli.on_conflict != ConflictPolicy::KeepFirst,
+            sqlite,
+        ),
+        (
+            "--raw-json",
+            cli.raw_json.is_some(),
+            cli.output_format != OutputFormat::Jsonl,
+        ),
+        (
+            "--partition",
+            cli.partition,
+            cli.output_format == OutputFormat::Parquet,
+        ),
+    ];
+    for (flag, used, supported) in options {
+        if used && !supported {
+            let format = cli
+                .output_format
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default();
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{flag} cannot be used with --output-format {format}"),
+                )
+                .exit();
+        }
+    }
+}
+
+fn verify(args: &VerifyArgs) {
+    validate_input_dir(args.comments.as_deref(), "comments");
+    validate_input_dir(args.submissions.as_deref(), "submission");
+    validate_input_dir(args.subreddits.as_deref(), "subreddits");
+    let manifest = Manifest::from_file(&args.manifest).expect("Error reading checksum manifest");
+    let files: Vec<PathBuf> = [
+        args.comments.as_deref(),
+        args.submissions.as_deref(),
+        args.subreddits.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .flat_map(get_file_list)
+    .collect();
+    let verified = checksum::verify_files(&manifest, &files).expect("Error hashing input files");
+    let failures = log_verification(&verified);
+    info!("Verified {} files, {} failed", verified.len(), failures);
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn inspect(args: &InspectArgs) {
+    let inspector =
+        inspect::inspect_file(&args.file, args.sample).expect("Error reading input file");
+    let report = inspector.report(args.top);
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("Error serializing report")
+    );
+}
+
+fn fts(args: &FtsArgs) {
+    match &args.command {
+        FtsCommand::Rebuild { database, index } => sqlite::rebuild_fts(database, &index.options())
+            .expect("Error rebuilding full text search index"),
+    }
+}
+
+/// Log the outcome for each verified file, returning the number of files that did not match.
+fn log_verification(verified: &[VerifiedFile]) -> usize {
+    let mut failures = 0;
+    for file in verified {
+        let filename = file.path.display();
+        match file.status {
+            VerificationStatus::Match => {
+                info!(filename:%, sha256 = file.sha256; "Checksum verified")
+            }
+            VerificationStatus::Mismatch => {
+                failures += 1;
+                error!(filename:%, sha256 = file.sha256, expected = file.expected; "Checksum mismatch")
+            }
+            VerificationStatus::Unlisted => {
+                failures += 1;
+                warn!(filename:%, sha256 = file.sha256; "File not listed in checksum manifest")
+            }
+        }
+    }
+    failures
+}
+
+fn validate_input_dir(dir: Option<&Path>, name: &str) {
+    if Some(false) == dir.map(|path| path.exists() && path.is_dir()) {
+        Cli::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!("{name} must be a directory and exist"),
+            )
+            .exit();
     }
 }
 
-fn process<T, U>(file_list: Vec<PathBuf>, filter: Arc<Filter>, db: &mut T)
+fn process<T, U>(
+    file_list: Vec<PathBuf>,
+    filter: Arc<Filter>,
+    options: ImportOptions,
+    db: &mut T,
+) -> (usize, SchemaDrift)
 where
     T: Storage,
-    U: Storable + Filterable + for<'a> Deserialize<'a> + Send + 'static,
+    U: Storable + Filterable + UnknownVariants + for<'a> Deserialize<'a> + Send + 'static,
 {
     let shared_file_list = Arc::new(RwLock::new(file_list));
     let completed = Arc::new(AtomicUsize::new(0));
+    let drift = Arc::new(Mutex::new(SchemaDrift::default()));
     let mut threads = Vec::new();
     let (tx, rx) = mpsc::sync_channel(10000);
-    let num_cpus = num_cpus::get_physical();
-    for _i in 0..(num_cpus - 1) {
+    // Leave a core for storing, but always start a worker
+    let workers = num_cpus::get_physical().saturating_sub(1).max(1);
+    for _i in 0..workers {
         let filter_context = ThreadContext::new(
             filter.clone(),
+            options,
             shared_file_list.clone(),
             completed.clone(),
+            drift.clone(),
             tx.clone(),
         );
         let thread = thread::spawn(move || {
@@ -173,7 +726,7 @@ where
                 maybe_content.unwrap();
             }
             Err(mpsc::TryRecvError::Empty) => {
-                if completed.load(Ordering::Relaxed) < (num_cpus - 1) {
+                if completed.load(Ordering::Relaxed) < workers {
                     thread::sleep(time::Duration::from_secs(1));
                 } else {
                     break;
@@ -187,6 +740,12 @@ where
     for thread in threads {
         thread.join().expect("threads to join");
     }
+
+    let drift = Arc::into_inner(drift)
+        .expect("threads to be joined")
+        .into_inner()
+        .expect("drift lock");
+    (count, drift)
 }
 
 fn get_file_list(dir: &Path) -> Vec<PathBuf> {
@@ -198,25 +757,31 @@ fn get_file_list(dir: &Path) -> Vec<PathBuf> {
 
 struct ThreadContext<T> {
     filter: Arc<Filter>,
+    options: ImportOptions,
     queue: Arc<RwLock<Vec<PathBuf>>>,
     completed: Arc<AtomicUsize>,
+    drift: Arc<Mutex<SchemaDrift>>,
     send_channel: mpsc::SyncSender<T>,
 }
 
 impl<T> ThreadContext<T>
 where
-    T: for<'a> Deserialize<'a> + Filterable,
+    T: for<'a> Deserialize<'a> + Filterable + Storable + UnknownVariants,
 {
     fn new(
         filter: Arc<Filter>,
+        options: ImportOptions,
         queue: Arc<RwLock<Vec<PathBuf>>>,
         completed: Arc<AtomicUsize>,
+        drift: Arc<Mutex<SchemaDrift>>,
         send_channel: mpsc::SyncSender<T>,
     ) -> Self {
         ThreadContext {
             filter,
+            options,
             queue,
             completed,
+            drift,
             send_channel,
         }
     }
@@ -227,7 +792,10 @@ where
     }
 
     fn process_queue(&self) {
+        let mut drift = SchemaDrift::default();
+        let mut parser = JsonParser::default();
         while let Some(filename) = self.get_next_file() {
+            let source_file: Arc<str> = filename.to_string_lossy().into();
             let mut lines = match decompress::stream_lines(filename.as_path()) {
                 Ok(l) => l,
                 Err(err) => {
@@ -248,20 +816,113 @@ where
                 };
                 // Remove leading and trailing non-json chars
                 let line = line.trim_matches(|ch| !(ch == '{' || ch == '}'));
-                let content = match serde_json::from_str::<T>(line) {
+                // Reject lines using a cheap borrowed view before deserializing the full content. Lines the view
+                // cannot parse fall through to full deserialization, which reports the error.
+                if T::PRE_FILTER
+                    && !self.filter.is_empty()
+                    && let Ok(view) = parser.parse::<FilterView>(line)
+                    && !self.filter.filter(&view)
+                {
+                    continue;
+                }
+                let content: Result<T> = if self.options.drift_report {
+                    serde_json::from_str::<Value>(line)
+                        .and_then(|value| drift.deserialize(&value))
+                        .map_err(Into::into)
+                } else {
+                    parser.parse(line).map_err(Into::into)
+                };
+                let content = match content {
+                    Err(_) if self.options.lenient => serde_json::from_str(line)
+                        .and_then(|value| {
+                            deser::deserialize_lenient(value, |key| {
+                                drift.record_discarded_field(key)
+                            })
+                        })
+                        .map_err(Into::into),
+                    content => content,
+                };
+                let mut content: T = match content {
                     Ok(data) => data,
                     Err(err) => {
                         error!(err:?, filename:% = filename.display(), json = line; "Error deserializing content");
                         continue;
                     }
                 };
-                if self.filter.filter(&content) {
-                    self.send_channel.send(content).unwrap_or_else(|_| {
-                        panic!("failed to parse line from {}", filename.display())
-                    });
+                let unknown_variants = content.unknown_variants();
+                if let Some((field, value)) = unknown_variants.first()
+                    && !self.options.lenient
+                {
+                    error!(filename:% = filename.display(), field, value, json = line; "Unknown enum value in content");
+                    continue;
+                }
+                for (field, value) in unknown_variants {
+                    drift.record_unknown_variant(field, value);
                 }
+                if !self.filter.filter(&content) {
+                    continue;
+                }
+                if let Some(format) = self.options.raw_json {
+                    match format.encode(line) {
+                        Ok(raw_json) => content.set_raw_json(raw_json),
+                        Err(err) => {
+                            error!(err:?, filename:% = filename.display(); "Error encoding raw json");
+                            continue;
+                        }
+                    }
+                }
+                if self.options.revisions {
+                    content.set_source_file(source_file.clone());
+                }
+                self.send_channel
+                    .send(content)
+                    .unwrap_or_else(|_| panic!("failed to parse line from {}", filename.display()));
             }
         }
+        self.drift.lock().expect("drift lock").merge(drift);
         self.completed.fetch_add(1, Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects the display names of stored subreddits.
+    #[derive(Default)]
+    struct Subreddits(Vec<String>);
+
+    impl Storage for Subreddits {
+        fn insert_comment(&mut self, _comment: Comment) -> Result<usize> {
+            Ok(0)
+        }
+        fn insert_submission(&mut self, _submission: Submission) -> Result<usize> {
+            Ok(0)
+        }
+        fn insert_subreddit(&mut self, subreddit: Subreddit) -> Result<usize> {
+            self.0.push(subreddit.display_name);
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_process_subreddit_filter() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("subreddits.zst");
+        std::fs::write(
+            &path,
+            zstd::encode_all(
+                include_str!("../test_data/test_subreddits.json").as_bytes(),
+                0,
+            )?,
+        )?;
+        let cli = Cli::parse_from(["import", "out.db", "--subreddit", "science"]);
+        let filter = Arc::new(Filter::from_cli(&cli).subreddit_filter());
+        let mut storage = Subreddits::default();
+        let (count, _) =
+            process::<_, Subreddit>(vec![path], filter, ImportOptions::default(), &mut storage);
+        assert_eq!(count, 1);
+        assert_eq!(storage.0, ["science"]);
+        Ok(())
+    }
+}
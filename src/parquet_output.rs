@@ -0,0 +1,514 @@
+//! Writes comments and submissions to Parquet files, with the columns of the Sqlite schema.
+//!
+//! Each kind of item is written to its own directory as a series of part files. Items are buffered until a row group
+//! is full, and a part file is closed once it holds `row_groups_per_file` row groups, so files stay a manageable size
+//! for query engines to split between workers.
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow};
+use arrow_array::{
+    ArrayRef, RecordBatch,
+    builder::{BinaryBuilder, BooleanBuilder, Int64Builder, StringBuilder},
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use rusqlite::types::ValueRef;
+
+use crate::{
+    reddit_types::{RawJson, comment::Comment, submission::Submission, subreddit::Subreddit},
+    sqlite::{COMMENT_COLUMNS, Column, ColumnSelection, SUBMISSION_COLUMNS, value_ref},
+    storage::Storage,
+};
+
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 100_000;
+pub const DEFAULT_ROW_GROUPS_PER_FILE: usize = 10;
+
+/// Settings for writing Parquet files.
+#[derive(Debug, Clone)]
+pub struct ParquetOptions {
+    /// Rows in each row group
+    pub row_group_size: usize,
+    /// Row groups in each part file
+    pub row_groups_per_file: usize,
+    /// Store the raw JSON of each item in a raw_json column
+    pub raw_json: bool,
+    pub comment_columns: ColumnSelection,
+    pub submission_columns: ColumnSelection,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        ParquetOptions {
+            row_group_size: DEFAULT_ROW_GROUP_SIZE,
+            row_groups_per_file: DEFAULT_ROW_GROUPS_PER_FILE,
+            raw_json: false,
+            comment_columns: ColumnSelection::default(),
+            submission_columns: ColumnSelection::default(),
+        }
+    }
+}
+
+impl ParquetOptions {
+    /// The options for each of `max_open` partitions written at once, with row groups small enough that at most
+    /// `buffered_rows` rows are held in memory across all of them.
+    pub fn partitioned(mut self, max_open: usize, buffered_rows: usize) -> Self {
+        self.row_group_size = self
+            .row_group_size
+            .min(buffered_rows / max_open.max(1))
+            .max(1);
+        self
+    }
+}
+
+/// An item that can be written as a row of a Parquet file.
+pub trait ParquetRow: Sized + 'static {
+    /// The directory items of this kind are written to
+    const KIND: &'static str;
+    fn registry() -> &'static [Column<Self>];
+    fn selection(options: &ParquetOptions) -> &ColumnSelection;
+    fn raw_json(&self) -> Option<&RawJson>;
+}
+
+impl ParquetRow for Comment {
+    const KIND: &'static str = "comment";
+
+    fn registry() -> &'static [Column<Self>] {
+        COMMENT_COLUMNS
+    }
+
+    fn selection(options: &ParquetOptions) -> &ColumnSelection {
+        &options.comment_columns
+    }
+
+    fn raw_json(&self) -> Option<&RawJson> {
+        self.raw_json.as_ref()
+    }
+}
+
+impl ParquetRow for Submission {
+    const KIND: &'static str = "submission";
+
+    fn registry() -> &'static [Column<Self>] {
+        SUBMISSION_COLUMNS
+    }
+
+    fn selection(options: &ParquetOptions) -> &ColumnSelection {
+        &options.submission_columns
+    }
+
+    fn raw_json(&self) -> Option<&RawJson> {
+        self.raw_json.as_ref()
+    }
+}
+
+/// Writes items of one kind to numbered part files in a directory.
+///
+/// Part numbering continues after any part files already in the directory, so writing to a directory again adds to
+/// it rather than overwriting it.
+pub struct ParquetWriter<T: ParquetRow> {
+    directory: PathBuf,
+    columns: Vec<&'static Column<T>>,
+    schema: SchemaRef,
+    properties: WriterProperties,
+    row_group_size: usize,
+    row_groups_per_file: usize,
+    raw_json: bool,
+    buffer: Vec<T>,
+    writer: Option<ArrowWriter<File>>,
+    row_groups: usize,
+    next_part: usize,
+}
+
+impl<T: ParquetRow> ParquetWriter<T> {
+    /// A writer for `directory`, which is created when the first row group is written.
+    pub fn new(directory: &Path, options: &ParquetOptions) -> Result<Self> {
+        if options.row_group_size == 0 || options.row_groups_per_file == 0 {
+            return Err(anyhow!(
+                "row groups and part files must hold at least one row"
+            ));
+        }
+        let columns = T::selection(options).resolve(T::registry(), &[], &[])?;
+        let mut fields: Vec<Field> = columns
+            .iter()
+            .map(|column| {
+                Field::new(
+                    column.name,
+                    data_type(column.definition),
+                    nullable(column.definition),
+                )
+            })
+            .collect();
+        if options.raw_json {
+            fields.push(Field::new("raw_json", DataType::Binary, true));
+        }
+        Ok(ParquetWriter {
+            directory: directory.to_path_buf(),
+            columns,
+            schema: Arc::new(Schema::new(fields)),
+            properties: WriterProperties::builder()
+                .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                .set_max_row_group_size(options.row_group_size)
+                .build(),
+            row_group_size: options.row_group_size,
+            row_groups_per_file: options.row_groups_per_file,
+            raw_json: options.raw_json,
+            // Grown as items arrive, as most partitions never fill a row group
+            buffer: Vec::new(),
+            writer: None,
+            row_groups: 0,
+            next_part: next_part(directory)?,
+        })
+    }
+
+    pub fn write(&mut self, item: T) -> Result<()> {
+        self.buffer.push(item);
+        if self.buffer.len() >= self.row_group_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered items as a row group, closing the part file once it is full.
+    fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let batch = self.record_batch()?;
+        self.buffer.clear();
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                fs::create_dir_all(&self.directory).with_context(|| {
+                    format!("unable to create directory {}", self.directory.display())
+                })?;
+                let path = self
+                    .directory
+                    .join(format!("part-{:05}.parquet", self.next_part));
+                self.next_part += 1;
+                let file = File::create(&path)
+                    .with_context(|| format!("unable to create {}", path.display()))?;
+                self.writer.insert(ArrowWriter::try_new(
+                    file,
+                    self.schema.clone(),
+                    Some(self.properties.clone()),
+                )?)
+            }
+        };
+        writer.write(&batch)?;
+        writer.flush()?;
+        self.row_groups += 1;
+        if self.row_groups >= self.row_groups_per_file {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        self.row_groups = 0;
+        Ok(())
+    }
+
+    /// Write any buffered items and close the current part file.
+    pub fn finish(&mut self) -> Result<()> {
+        self.flush()?;
+        self.close()
+    }
+
+    fn record_batch(&self) -> Result<RecordBatch> {
+        let mut arrays = Vec::with_capacity(self.schema.fields().len());
+        for column in &self.columns {
+            arrays.push(column_array(column, &self.buffer)?);
+        }
+        if self.raw_json {
+            let mut builder = BinaryBuilder::new();
+            for item in &self.buffer {
+                builder.append_option(item.raw_json().map(|raw_json| match raw_json {
+                    RawJson::Plain(line) => line.as_bytes(),
+                    RawJson::Zstd(compressed) => compressed.as_slice(),
+                }));
+            }
+            arrays.push(Arc::new(builder.finish()));
+        }
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+}
+
+impl<T: ParquetRow> Drop for ParquetWriter<T> {
+    fn drop(&mut self) {
+        // A part file is unreadable until its footer is written
+        let _ = self.finish();
+    }
+}
+
+/// The arrow type of a column, from the Sqlite type its definition starts with.
+fn data_type(definition: &str) -> DataType {
+    match definition.split_whitespace().next() {
+        Some("INTEGER") => DataType::Int64,
+        Some("BOOLEAN") => DataType::Boolean,
+        Some("BLOB") => DataType::Binary,
+        _ => DataType::Utf8,
+    }
+}
+
+fn nullable(definition: &str) -> bool {
+    !(definition.contains("NOT NULL") || definition.contains("PRIMARY KEY"))
+}
+
+fn column_array<T>(column: &Column<T>, items: &[T]) -> Result<ArrayRef> {
+    let mismatch = |value: ValueRef| {
+        anyhow!(
+            "unexpected {:?} value for the {} column",
+            value.data_type(),
+            column.name
+        )
+    };
+    Ok(match data_type(column.definition) {
+        DataType::Int64 => {
+            let mut builder = Int64Builder::with_capacity(items.len());
+            for item in items {
+                match value_ref(&(column.value)(item)?) {
+                    ValueRef::Null => builder.append_null(),
+                    ValueRef::Integer(value) => builder.append_value(value),
+                    value => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(items.len());
+            for item in items {
+                match value_ref(&(column.value)(item)?) {
+                    ValueRef::Null => builder.append_null(),
+                    ValueRef::Integer(value) => builder.append_value(value != 0),
+                    value => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Binary => {
+            let mut builder = BinaryBuilder::new();
+            for item in items {
+                match value_ref(&(column.value)(item)?) {
+                    ValueRef::Null => builder.append_null(),
+                    ValueRef::Blob(value) | ValueRef::Text(value) => builder.append_value(value),
+                    value => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        _ => {
+            let mut builder = StringBuilder::new();
+            for item in items {
+                match value_ref(&(column.value)(item)?) {
+                    ValueRef::Null => builder.append_null(),
+                    ValueRef::Text(value) => builder.append_value(std::str::from_utf8(value)?),
+                    value => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    })
+}
+
+/// The number after the highest numbered part file in `directory`.
+fn next_part(directory: &Path) -> Result<usize> {
+    if !directory.exists() {
+        return Ok(0);
+    }
+    let mut next = 0;
+    for entry in fs::read_dir(directory)? {
+        let name = entry?.file_name();
+        if let Some(part) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("part-"))
+            .and_then(|name| name.strip_suffix(".parquet"))
+            .and_then(|part| part.parse::<usize>().ok())
+        {
+            next = next.max(part + 1);
+        }
+    }
+    Ok(next)
+}
+
+/// Writes comments and submissions to the comment and submission directories of an output directory.
+pub struct Parquet {
+    comments: Option<ParquetWriter<Comment>>,
+    submissions: Option<ParquetWriter<Submission>>,
+}
+
+impl Parquet {
+    pub fn new(directory: &Path, options: ParquetOptions) -> Result<Self> {
+        Ok(Parquet {
+            comments: Some(ParquetWriter::new(
+                &directory.join(Comment::KIND),
+                &options,
+            )?),
+            submissions: Some(ParquetWriter::new(
+                &directory.join(Submission::KIND),
+                &options,
+            )?),
+        })
+    }
+
+    /// Write items of `kind` directly to `directory`, for a partition, which only ever receives one kind.
+    pub fn partition(kind: &str, directory: &Path, options: &ParquetOptions) -> Result<Self> {
+        Ok(Parquet {
+            comments: (kind == Comment::KIND)
+                .then(|| ParquetWriter::new(directory, options))
+                .transpose()?,
+            submissions: (kind == Submission::KIND)
+                .then(|| ParquetWriter::new(directory, options))
+                .transpose()?,
+        })
+    }
+}
+
+impl Storage for Parquet {
+    fn insert_comment(&mut self, comment: Comment) -> Result<usize> {
+        self.comments
+            .as_mut()
+            .ok_or_else(|| anyhow!("this Parquet output does not hold comments"))?
+            .write(comment)?;
+        Ok(0)
+    }
+
+    fn insert_submission(&mut self, submission: Submission) -> Result<usize> {
+        self.submissions
+            .as_mut()
+            .ok_or_else(|| anyhow!("this Parquet output does not hold submissions"))?
+            .write(submission)?;
+        Ok(0)
+    }
+
+    fn insert_subreddit(&mut self, _subreddit: Subreddit) -> Result<usize> {
+        Err(anyhow!(
+            "subreddit metadata can only be imported into Sqlite"
+        ))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(comments) = &mut self.comments {
+            comments.finish()?;
+        }
+        if let Some(submissions) = &mut self.submissions {
+            submissions.finish()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, Int64Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_row_groups() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let directory = temp.path();
+        let options = ParquetOptions {
+            row_group_size: 2,
+            row_groups_per_file: 2,
+            comment_columns: ColumnSelection {
+                columns: Some(vec!["body".to_string(), "score".to_string()]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let comments: Vec<Comment> = include_str!("../test_data/test_comments.json")
+            .lines()
+            .take(5)
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        let mut parquet = Parquet::new(directory, options)?;
+        for comment in comments.iter().cloned() {
+            parquet.insert_comment(comment)?;
+        }
+        parquet.finish()?;
+
+        let first = File::open(directory.join("comment/part-00000.parquet"))?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(first)?;
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        let names: Vec<_> = reader
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "decoded_reddit_id",
+                "reddit_id",
+                "body",
+                "score",
+                "created_utc"
+            ]
+        );
+        let batch = reader.build()?.next().transpose()?.expect("a row group");
+        let ids = batch.column(0).as_any().downcast_ref::<Int64Array>();
+        assert_eq!(
+            ids.map(|ids| ids.value(1)),
+            Some(i64::from_str_radix(&comments[1].id, 36)?)
+        );
+        let bodies = batch.column(2).as_any().downcast_ref::<StringArray>();
+        assert_eq!(
+            bodies.map(|bodies| bodies.value(0)),
+            Some(comments[0].body.as_str())
+        );
+
+        let last = File::open(directory.join("comment/part-00001.parquet"))?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(last)?;
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 1);
+        assert!(!directory.join("submission").exists());
+
+        // Writing again adds part files
+        let mut parquet = Parquet::new(directory, ParquetOptions::default())?;
+        parquet.insert_comment(comments[0].clone())?;
+        parquet.finish()?;
+        assert!(directory.join("comment/part-00002.parquet").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition() -> Result<()> {
+        let options = ParquetOptions::default().partitioned(128, 1_000_000);
+        assert_eq!(options.row_group_size, 7812);
+        assert_eq!(options.clone().partitioned(1_000, 100).row_group_size, 1);
+
+        let directory = tempfile::tempdir()?;
+        let comment: Comment = serde_json::from_str(
+            include_str!("../test_data/test_comments.json")
+                .lines()
+                .next()
+                .unwrap_or_default(),
+        )?;
+        let submission: Submission = serde_json::from_str(
+            include_str!("../test_data/test_submissions.json")
+                .lines()
+                .next()
+                .unwrap_or_default(),
+        )?;
+        // A partition only has a writer for its own kind
+        let mut parquet = Parquet::partition("comment", directory.path(), &options)?;
+        assert!(parquet.submissions.is_none());
+        assert!(parquet.insert_submission(submission).is_err());
+        parquet.insert_comment(comment)?;
+        parquet.finish()?;
+        assert!(directory.path().join("part-00000.parquet").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Lays out output as Hive style partitions, so query engines can skip the subreddits and months a query excludes.
+//!
+//! Each comment and submission is routed to the storage for its partition,
+//! `<kind>/subreddit=<subreddit>/year=<year>/month=<month>`, under the output directory. Only a bounded number of
+//! partition storages are kept open. When another is needed, the least recently used one is finished and closed, and
+//! reopening its partition later adds new files to it.
+
+use std::path::{Path, PathBuf};
+
+use ahash::HashMap;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike};
+use log::info;
+
+use crate::{
+    filter::Filterable,
+    reddit_types::{comment::Comment, submission::Submission, subreddit::Subreddit},
+    storage::Storage,
+};
+
+pub const DEFAULT_MAX_OPEN_PARTITIONS: usize = 128;
+pub const DEFAULT_PARTITION_BUFFER_ROWS: usize = 1_000_000;
+
+/// The partition value Hive uses for a missing key.
+const DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+struct OpenPartition<S> {
+    storage: S,
+    last_used: u64,
+}
+
+/// Routes items to a storage per partition, opened by `open` with the kind of item and the partition directory.
+pub struct Partitioned<S, F> {
+    root: PathBuf,
+    open: F,
+    max_open: usize,
+    partitions: HashMap<PathBuf, OpenPartition<S>>,
+    uses: u64,
+    evictions: usize,
+}
+
+impl<S, F> Partitioned<S, F>
+where
+    S: Storage,
+    F: FnMut(&str, &Path) -> Result<S>,
+{
+    pub fn new(root: &Path, max_open: usize, open: F) -> Result<Self> {
+        if max_open == 0 {
+            return Err(anyhow!("at least one partition must be open at a time"));
+        }
+        Ok(Partitioned {
+            root: root.to_path_buf(),
+            open,
+            max_open,
+            partitions: HashMap::default(),
+            uses: 0,
+            evictions: 0,
+        })
+    }
+
+    /// The storage for the partition of `item`, opening it if needed.
+    fn storage<T: Filterable>(&mut self, kind: &str, item: &T) -> Result<&mut S> {
+        let directory = self.root.join(partition_path(kind, item)?);
+        self.uses += 1;
+        if !self.partitions.contains_key(&directory) {
+            if self.partitions.len() >= self.max_open {
+                self.close_least_recently_used()?;
+            }
+            let storage = (self.open)(kind, &directory)?;
+            self.partitions.insert(
+                directory.clone(),
+                OpenPartition {
+                    storage,
+                    last_used: 0,
+                },
+            );
+        }
+        let partition = self
+            .partitions
+            .get_mut(&directory)
+            .expect("partition to be open");
+        partition.last_used = self.uses;
+        Ok(&mut partition.storage)
+    }
+
+    fn close_least_recently_used(&mut self) -> Result<()> {
+        let directory = self
+            .partitions
+            .iter()
+            .min_by_key(|(_, partition)| partition.last_used)
+            .map(|(directory, _)| directory.clone());
+        if let Some(mut partition) =
+            directory.and_then(|directory| self.partitions.remove(&directory))
+        {
+            partition.storage.finish()?;
+            self.evictions += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<S, F> Storage for Partitioned<S, F>
+where
+    S: Storage,
+    F: FnMut(&str, &Path) -> Result<S>,
+{
+    fn insert_comment(&mut self, comment: Comment) -> Result<usize> {
+        self.storage("comment", &comment)?.insert_comment(comment)
+    }
+
+    fn insert_submission(&mut self, submission: Submission) -> Result<usize> {
+        self.storage("submission", &submission)?
+            .insert_submission(submission)
+    }
+
+    fn insert_subreddit(&mut self, _subreddit: Subreddit) -> Result<usize> {
+        Err(anyhow!("subreddit metadata cannot be partitioned"))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (_, mut partition) in self.partitions.drain() {
+            partition.storage.finish()?;
+        }
+        if self.evictions > 0 {
+            info!(
+                "Closed partitions {} times to stay within the open partition limit. Raising the limit writes fewer, \
+                 larger files",
+                self.evictions
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The directory of the partition `item` belongs to, relative to the output directory.
+fn partition_path<T: Filterable>(kind: &str, item: &T) -> Result<PathBuf> {
+    let created = DateTime::from_timestamp(item.created(), 0)
+        .ok_or_else(|| anyhow!("created_utc {} is out of range", item.created()))?;
+    let subreddit = item
+        .subreddit()
+        .filter(|subreddit| !subreddit.is_empty())
+        .map_or_else(|| DEFAULT_PARTITION.to_string(), escape);
+    Ok([
+        kind.to_string(),
+        format!("subreddit={subreddit}"),
+        format!("year={}", created.year()),
+        format!("month={}", created.month()),
+    ]
+    .iter()
+    .collect())
+}
+
+/// Percent encode characters that could not appear in a partition directory name, as Hive does.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Records the partitions opened and the comments written to each.
+    #[derive(Default)]
+    struct Log {
+        opened: Vec<PathBuf>,
+        finished: usize,
+        comments: Vec<(PathBuf, String)>,
+    }
+
+    struct MockStorage {
+        directory: PathBuf,
+        log: Rc<RefCell<Log>>,
+    }
+
+    impl Storage for MockStorage {
+        fn insert_comment(&mut self, comment: Comment) -> Result<usize> {
+            self.log
+                .borrow_mut()
+                .comments
+                .push((self.directory.clone(), comment.id));
+            Ok(0)
+        }
+        fn insert_submission(&mut self, _submission: Submission) -> Result<usize> {
+            Ok(0)
+        }
+        fn insert_subreddit(&mut self, _subreddit: Subreddit) -> Result<usize> {
+            Ok(0)
+        }
+        fn finish(&mut self) -> Result<()> {
+            self.log.borrow_mut().finished += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_partitioned() -> Result<()> {
+        let log = Rc::new(RefCell::new(Log::default()));
+        let mut partitioned =
+            Partitioned::new(Path::new("out"), 1, |_kind: &str, directory: &Path| {
+                log.borrow_mut().opened.push(directory.to_path_buf());
+                Ok(MockStorage {
+                    directory: directory.to_path_buf(),
+                    log: log.clone(),
+                })
+            })?;
+        let comment: Comment = serde_json::from_str(
+            include_str!("../test_data/test_comments.json")
+                .lines()
+                .next()
+                .expect("a test comment"),
+        )?;
+        let mut other = comment.clone();
+        // 2006-02-01
+        other.created_utc = 1_138_752_000;
+        other.subreddit = "a/b".to_string();
+        let mut again = comment.clone();
+        again.id = "again".to_string();
+        let mut unknown = comment.clone();
+        unknown.subreddit = String::new();
+        for comment in [comment.clone(), other, again, unknown] {
+            partitioned.insert_comment(comment)?;
+        }
+        let evictions = partitioned.evictions;
+        partitioned.finish()?;
+
+        let log = log.borrow();
+        let first = Path::new("out").join(partition_path("comment", &comment)?);
+        assert!(
+            first
+                .to_string_lossy()
+                .starts_with("out/comment/subreddit=")
+        );
+        let created = DateTime::from_timestamp(comment.created_utc, 0).expect("a valid date");
+        let default = Path::new("out/comment/subreddit=__HIVE_DEFAULT_PARTITION__").join(format!(
+            "year={}/month={}",
+            created.year(),
+            created.month()
+        ));
+        // Only one partition is open at a time, so returning to the first reopens it
+        assert_eq!(
+            log.opened,
+            [
+                first.clone(),
+                PathBuf::from("out/comment/subreddit=a%2Fb/year=2006/month=2"),
+                first.clone(),
+                default.clone(),
+            ]
+        );
+        assert_eq!(evictions, 3);
+        assert_eq!(log.finished, 4);
+        assert_eq!(
+            log.comments,
+            [
+                (first.clone(), comment.id.clone()),
+                (log.opened[1].clone(), comment.id.clone()),
+                (first, "again".to_string()),
+                (default, comment.id.clone()),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//! How a database was produced, recorded in its metadata table so datasets can be audited and reproduced. Parquet
+//! and JSON Lines output record it in a metadata file in the output directory instead.
+
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{checksum::VerifiedFile, filter::Filter};
+
+/// The metadata file of an output directory. Query engines skip files starting with an underscore.
+pub const METADATA_FILE: &str = "_metadata.json";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InputFile {
+    pub path: String,
+    pub size: u64,
+    /// Only recorded when input files are hashed
+    pub sha256: Option<String>,
+}
+
+impl InputFile {
+    pub fn from_verified(file: &VerifiedFile) -> Self {
+        InputFile {
+            path: file.path.to_string_lossy().into_owned(),
+            size: file.size,
+            sha256: Some(file.sha256.clone()),
+        }
+    }
+
+    /// An input file recorded by size only.
+    pub fn unhashed(path: &Path) -> Result<Self> {
+        Ok(InputFile {
+            path: path.to_string_lossy().into_owned(),
+            size: fs::metadata(path)?.len(),
+            sha256: None,
+        })
+    }
+}
+
+/// One run of the importer. Each field is stored as a row of the metadata table.
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    pub importer_version: &'static str,
+    /// The command line, including the program name
+    pub arguments: Vec<String>,
+    pub filter: Filter,
+    pub input_files: Vec<InputFile>,
+    pub started_utc: i64,
+    pub finished_utc: i64,
+    /// Items that passed the filter and were stored, by kind. Items already in the database are counted too.
+    pub item_counts: BTreeMap<&'static str, usize>,
+}
+
+impl Provenance {
+    pub fn new(filter: Filter, input_files: Vec<InputFile>, started_utc: i64) -> Self {
+        Provenance {
+            importer_version: env!("CARGO_PKG_VERSION"),
+            arguments: std::env::args().collect(),
+            filter,
+            input_files,
+            started_utc,
+            finished_utc: started_utc,
+            item_counts: BTreeMap::new(),
+        }
+    }
+
+    /// Add this import to the metadata file of an output directory, a JSON array of the imports into it in order.
+    pub fn append_to_directory(&self, directory: &Path) -> Result<()> {
+        let path = directory.join(METADATA_FILE);
+        let mut imports: Vec<Value> = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .with_context(|| format!("unable to parse {}", path.display()))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        imports.push(serde_json::to_value(self)?);
+        // Nothing else is written to the directory when no items were imported
+        fs::create_dir_all(directory)?;
+        fs::write(&path, serde_json::to_vec_pretty(&imports)?)
+            .with_context(|| format!("unable to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_to_directory() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let output = directory.path().join("output");
+        let mut provenance = Provenance::new(Filter::default(), Vec::new(), 1);
+        provenance.item_counts.insert("comment", 3);
+        provenance.append_to_directory(&output)?;
+        provenance.started_utc = 2;
+        provenance.append_to_directory(&output)?;
+
+        let imports: Vec<Value> = serde_json::from_slice(&fs::read(output.join(METADATA_FILE))?)?;
+        assert_eq!(imports.len(), 2);
+        assert_eq!(imports[0]["started_utc"], 1);
+        assert_eq!(imports[1]["started_utc"], 2);
+        assert_eq!(imports[1]["item_counts"]["comment"], 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// An entry in the `all_awardings` list of a comment or submission: an award and how many times the item received it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Awarding {
+    /// `gid_1` to `gid_3` for silver, gold and platinum, `award_<uuid>` for later awards
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub award_type: Option<String>,
+    pub coin_price: Option<i64>,
+    pub coin_reward: Option<i64>,
+    pub days_of_premium: Option<i64>,
+    pub icon_url: Option<String>,
+    /// The subreddit a community award belongs to
+    pub subreddit_id: Option<String>,
+    #[serde(default)]
+    pub count: i64,
+}
+
+impl Awarding {
+    pub fn decoded_subreddit_id(&self) -> Option<i64> {
+        let id = self.subreddit_id.as_deref()?;
+        i64::from_str_radix(id.trim_start_matches("t5_"), 36).ok()
+    }
+}
+
+/// The number of each award an item received, keyed by award id.
+///
+/// Items from before `all_awardings` existed only have `gildings`, which counts silver, gold and platinum under the
+/// same ids.
+pub fn award_counts<'a>(
+    all_awardings: &'a [Awarding],
+    gildings: Option<&'a BTreeMap<String, i64>>,
+) -> BTreeMap<&'a str, i64> {
+    let mut counts = BTreeMap::new();
+    for awarding in all_awardings {
+        *counts.entry(awarding.id.as_str()).or_default() += awarding.count;
+    }
+    for (id, count) in gildings.into_iter().flatten() {
+        if *count > 0 {
+            counts.entry(id.as_str()).or_insert(*count);
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_award_counts() -> serde_json::Result<()> {
+        let all_awardings: Vec<Awarding> = serde_json::from_str(
+            r#"[{"id": "gid_1", "name": "Silver", "coin_price": 100, "count": 2},
+                {"id": "award_5f123e3d-4f48-42f4-9c11-e98b566d5897", "name": "Wholesome", "count": 1,
+                 "subreddit_id": "t5_2qh1i", "award_type": "community"}]"#,
+        )?;
+        assert_eq!(
+            all_awardings[1].decoded_subreddit_id(),
+            Some(i64::from_str_radix("2qh1i", 36).unwrap())
+        );
+
+        let gildings: BTreeMap<String, i64> =
+            serde_json::from_str(r#"{"gid_1": 2, "gid_2": 1, "gid_3": 0}"#)?;
+        let counts = award_counts(&all_awardings, Some(&gildings));
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            [
+                ("award_5f123e3d-4f48-42f4-9c11-e98b566d5897", 1),
+                ("gid_1", 2),
+                ("gid_2", 1)
+            ]
+        );
+        assert!(award_counts(&[], None).is_empty());
+        Ok(())
+    }
+}
@@ -1,12 +1,19 @@
+use std::{collections::BTreeMap, sync::Arc};
+
 use crate::{
     Filterable,
     deser::{deserialize_optional_time, deserialize_score, deserialize_time},
-    reddit_types::{ParentId, SubredditType},
+    reddit_types::{
+        ParentId, RawJson, SubredditType, UnknownVariants,
+        award::Awarding,
+        deletion::DeletionState,
+        flair::{Flair, FlairRichtext},
+    },
     storage::{Storable, Storage},
 };
 use anyhow::Result;
 use serde::Deserialize;
-use serde_with::{NoneAsEmptyString, serde_as};
+use serde_with::{DefaultOnNull, NoneAsEmptyString, serde_as};
 use strum::IntoStaticStr;
 use uuid::Uuid;
 
@@ -19,17 +26,23 @@ pub struct Comment {
     pub subreddit_id: ParentId,
     pub subreddit_type: Option<SubredditType>,
     #[serde(default)]
-    #[expect(dead_code)]
     pub author_flair_text: Option<String>,
-    #[expect(dead_code)]
     #[serde(default)]
     #[serde_as(as = "NoneAsEmptyString")]
     pub author_flair_template_id: Option<Uuid>,
     #[serde(default)]
     pub author_premium: Option<bool>,
     #[serde(default)]
-    #[expect(dead_code)]
     pub author_flair_css_class: Option<String>,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub author_flair_richtext: Vec<FlairRichtext>,
+    #[serde(default)]
+    pub author_flair_background_color: Option<String>,
+    #[serde(default)]
+    pub author_flair_text_color: Option<String>,
+    #[serde(default)]
+    pub author_flair_type: Option<String>,
     #[serde(deserialize_with = "deserialize_score")]
     pub score: Option<i64>,
     pub ups: Option<i32>,
@@ -56,21 +69,63 @@ pub struct Comment {
     pub edited: Option<i64>,
     #[serde(default)]
     pub archived: bool,
-    #[expect(dead_code)]
     pub controversiality: Option<i32>,
     #[serde(default)]
+    pub score_hidden: Option<bool>,
+    #[serde(default)]
+    pub body_sha1: Option<String>,
+    /// The author's id, prefixed with `t2_`
+    #[serde(default)]
+    pub author_fullname: Option<String>,
+    /// When the comment was last updated in the dump, which may be after it was retrieved
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    pub updated_on: Option<i64>,
+    #[serde(default)]
     pub locked: bool,
     #[serde(default)]
     pub collapsed: bool,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub all_awardings: Vec<Awarding>,
+    /// Silver, gold and platinum counts keyed by award id, from before `all_awardings` existed
+    #[serde(default)]
+    pub gildings: Option<BTreeMap<String, i64>>,
+    #[serde(default)]
+    pub gilded: Option<i64>,
+    #[serde(default)]
+    pub total_awards_received: Option<i64>,
+    /// The line this item was deserialized from, if raw JSON is being kept
+    #[serde(skip)]
+    pub raw_json: Option<RawJson>,
+    /// The input file this item was read from, if revisions are being kept
+    #[serde(skip)]
+    pub source_file: Option<Arc<str>>,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, IntoStaticStr)]
+#[derive(Deserialize, Debug, Clone, IntoStaticStr)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub(crate) enum Distinguished {
     Admin,
     Moderator,
     Special,
+    /// A value not known to the importer, kept verbatim
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl Comment {
+    pub fn author_flair(&self) -> Flair<'_> {
+        Flair {
+            template_id: self.author_flair_template_id,
+            text: self.author_flair_text.as_deref(),
+            css_class: self.author_flair_css_class.as_deref(),
+            richtext: &self.author_flair_richtext,
+            background_color: self.author_flair_background_color.as_deref(),
+            text_color: self.author_flair_text_color.as_deref(),
+            flair_type: self.author_flair_type.as_deref(),
+        }
+    }
 }
 
 impl Filterable for Comment {
@@ -86,12 +141,40 @@ impl Filterable for Comment {
     fn created(&self) -> i64 {
         self.created_utc
     }
+    fn deletion_state(&self) -> Option<DeletionState> {
+        Some(DeletionState::classify(
+            Some(&self.author),
+            Some(&self.body),
+            None,
+        ))
+    }
+}
+
+impl UnknownVariants for Comment {
+    fn unknown_variants(&self) -> Vec<(&'static str, &str)> {
+        let mut unknown = Vec::new();
+        if let Some(ty) = self.subreddit_type.as_ref().filter(|ty| ty.is_other()) {
+            unknown.push(("subreddit_type", ty.as_str()));
+        }
+        if let Some(distinguished) = self.distinguished.as_ref().filter(|d| d.is_other()) {
+            unknown.push(("distinguished", distinguished.as_str()));
+        }
+        unknown
+    }
 }
 
 impl Storable for Comment {
     fn store<T: Storage>(self, storage: &mut T) -> Result<usize> {
         storage.insert_comment(self)
     }
+
+    fn set_raw_json(&mut self, raw_json: RawJson) {
+        self.raw_json = Some(raw_json);
+    }
+
+    fn set_source_file(&mut self, source_file: Arc<str>) {
+        self.source_file = Some(source_file);
+    }
 }
 
 #[cfg(test)]
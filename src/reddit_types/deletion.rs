@@ -0,0 +1,98 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use strum::IntoStaticStr;
+
+/// Whether, and by whom, a comment or submission was deleted or removed.
+///
+/// Pushshift keeps deleted and removed content as placeholder text, such as a body of `[removed]`, and only records why
+/// a submission was removed in `removed_by_category`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, IntoStaticStr, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum DeletionState {
+    /// Neither deleted nor removed
+    Present,
+    /// The author deleted their account, but the content remains
+    AuthorDeleted,
+    /// The author deleted the content
+    Deleted,
+    /// A moderator or AutoModerator removed the content
+    RemovedByModerator,
+    /// Reddit removed the content, such as for a copyright takedown or by its anti-evil operations team
+    RemovedByReddit,
+    /// The content was removed, but the dump does not record by whom
+    Removed,
+}
+
+impl DeletionState {
+    /// Classify an item from its author, its body or selftext, and the `removed_by_category` of submissions.
+    pub fn classify(
+        author: Option<&str>,
+        text: Option<&str>,
+        removed_by_category: Option<&str>,
+    ) -> Self {
+        match removed_by_category {
+            Some("author" | "deleted") => return DeletionState::Deleted,
+            Some("moderator" | "automod_filtered") => return DeletionState::RemovedByModerator,
+            Some(
+                "reddit" | "anti_evil_ops" | "community_ops" | "content_takedown"
+                | "copyright_takedown",
+            ) => return DeletionState::RemovedByReddit,
+            Some(_) => return DeletionState::Removed,
+            None => {}
+        }
+        match (text, author) {
+            (Some("[deleted]"), _) => DeletionState::Deleted,
+            (Some("[removed]"), _) => DeletionState::Removed,
+            (_, Some("[deleted]")) => DeletionState::AuthorDeleted,
+            _ => DeletionState::Present,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            DeletionState::classify(Some("snoo"), Some("hello"), None),
+            DeletionState::Present
+        );
+        assert_eq!(
+            DeletionState::classify(Some("[deleted]"), Some("hello"), None),
+            DeletionState::AuthorDeleted
+        );
+        assert_eq!(
+            DeletionState::classify(Some("[deleted]"), Some("[deleted]"), None),
+            DeletionState::Deleted
+        );
+        assert_eq!(
+            DeletionState::classify(Some("snoo"), Some("[removed]"), None),
+            DeletionState::Removed
+        );
+        assert_eq!(
+            DeletionState::classify(Some("snoo"), Some("[removed]"), Some("automod_filtered")),
+            DeletionState::RemovedByModerator
+        );
+        assert_eq!(
+            DeletionState::classify(Some("[deleted]"), Some(""), Some("anti_evil_ops")),
+            DeletionState::RemovedByReddit
+        );
+        assert_eq!(
+            DeletionState::classify(None, None, Some("something_new")),
+            DeletionState::Removed
+        );
+        assert_eq!(
+            DeletionState::RemovedByModerator.as_str(),
+            "removed_by_moderator"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+
+use serde::Deserialize;
+
+use crate::{
+    Filterable,
+    deser::{deserialize_optional_cow_str, deserialize_score, deserialize_time},
+    reddit_types::deletion::DeletionState,
+};
+
+/// The fields `Filterable` needs, borrowed from the input line where possible.
+///
+/// Deserializing a view is much cheaper than a full `Comment` or `Submission`, so lines are first parsed into a view
+/// and only fully deserialized if they pass the filter. Comments and submissions share the same field names, so one
+/// view serves both.
+#[derive(Deserialize, Debug)]
+pub struct FilterView<'a> {
+    #[serde(default, borrow, deserialize_with = "deserialize_optional_cow_str")]
+    author: Option<Cow<'a, str>>,
+    #[serde(default, borrow, deserialize_with = "deserialize_optional_cow_str")]
+    subreddit: Option<Cow<'a, str>>,
+    #[serde(default, deserialize_with = "deserialize_score")]
+    score: Option<i64>,
+    #[serde(deserialize_with = "deserialize_time")]
+    created_utc: i64,
+    /// The body of a comment
+    #[serde(default, borrow, deserialize_with = "deserialize_optional_cow_str")]
+    body: Option<Cow<'a, str>>,
+    #[serde(default, borrow, deserialize_with = "deserialize_optional_cow_str")]
+    selftext: Option<Cow<'a, str>>,
+    #[serde(default, borrow, deserialize_with = "deserialize_optional_cow_str")]
+    removed_by_category: Option<Cow<'a, str>>,
+}
+
+impl Filterable for FilterView<'_> {
+    fn score(&self) -> Option<i64> {
+        self.score
+    }
+    fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+    fn subreddit(&self) -> Option<&str> {
+        self.subreddit.as_deref()
+    }
+    fn created(&self) -> i64 {
+        self.created_utc
+    }
+    fn deletion_state(&self) -> Option<DeletionState> {
+        Some(DeletionState::classify(
+            self.author(),
+            self.body.as_deref().or(self.selftext.as_deref()),
+            self.removed_by_category.as_deref(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reddit_types::{comment::Comment, submission::Submission};
+
+    fn assert_same<T: Filterable>(view: &FilterView, content: &T) {
+        assert_eq!(view.score(), content.score());
+        assert_eq!(view.author(), content.author());
+        assert_eq!(view.subreddit(), content.subreddit());
+        assert_eq!(view.created(), content.created());
+        assert_eq!(view.deletion_state(), content.deletion_state());
+    }
+
+    #[test]
+    fn test_matches_content() {
+        let comments = include_str!("../../test_data/test_comments.json");
+        for line in comments.lines() {
+            let view: FilterView = serde_json::from_str(line).expect("view deserialization");
+            let comment: Comment = serde_json::from_str(line).expect("deserialization");
+            assert_same(&view, &comment);
+        }
+        let submissions = include_str!("../../test_data/test_submissions.json");
+        for line in submissions.lines() {
+            let view: FilterView = serde_json::from_str(line).expect("view deserialization");
+            let submission: Submission = serde_json::from_str(line).expect("deserialization");
+            assert_same(&view, &submission);
+        }
+    }
+
+    #[test]
+    fn test_borrows_unescaped_strings() {
+        let view: FilterView = serde_json::from_str(
+            r#"{"author": "snoo", "subreddit": "r\u0065ddit", "created_utc": 1}"#,
+        )
+        .expect("view deserialization");
+        assert!(matches!(view.author, Some(Cow::Borrowed("snoo"))));
+        assert!(matches!(view.subreddit, Some(Cow::Owned(_))));
+        assert_eq!(view.subreddit(), Some("reddit"));
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A part of a richtext flair: either text or an emoji.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FlairRichtext {
+    /// `text` or `emoji`
+    #[serde(rename = "e")]
+    pub kind: String,
+    #[serde(rename = "t", skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// The name of an emoji, such as `:snoo:`
+    #[serde(rename = "a", skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// The image of an emoji
+    #[serde(rename = "u", skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// The author or link flair of an item, borrowed from its `author_flair_*` or `link_flair_*` fields.
+#[derive(Debug, Clone, Copy)]
+pub struct Flair<'a> {
+    pub template_id: Option<Uuid>,
+    pub text: Option<&'a str>,
+    pub css_class: Option<&'a str>,
+    pub richtext: &'a [FlairRichtext],
+    pub background_color: Option<&'a str>,
+    /// `dark` or `light`
+    pub text_color: Option<&'a str>,
+    /// `text` or `richtext`
+    pub flair_type: Option<&'a str>,
+}
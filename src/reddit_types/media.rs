@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// The `gallery_data` of a gallery submission, listing its media in display order.
+#[derive(Deserialize, Debug, Clone)]
+pub struct GalleryData {
+    #[serde(default)]
+    pub items: Vec<GalleryItem>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GalleryItem {
+    /// Key of the item in `media_metadata`
+    pub media_id: String,
+    pub caption: Option<String>,
+    pub outbound_url: Option<String>,
+}
+
+/// An entry in `media_metadata`, describing media in a gallery or embedded in the text of a post.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MediaMetadata {
+    /// `valid` once the media has been processed
+    pub status: Option<String>,
+    /// The kind of media, such as `Image` or `AnimatedImage`
+    #[serde(rename = "e")]
+    pub kind: Option<String>,
+    #[serde(rename = "m")]
+    pub mime_type: Option<String>,
+    /// The full size version of the media
+    #[serde(rename = "s")]
+    pub source: Option<MediaSource>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MediaSource {
+    #[serde(rename = "x")]
+    pub width: Option<i64>,
+    #[serde(rename = "y")]
+    pub height: Option<i64>,
+    /// Set for images
+    #[serde(rename = "u")]
+    pub url: Option<String>,
+    /// Set for animated images instead of `url`
+    pub gif: Option<String>,
+}
+
+impl MediaSource {
+    /// The URL of the media, with the HTML escaping reddit applies removed.
+    pub fn url(&self) -> Option<String> {
+        let url = self.url.as_ref().or(self.gif.as_ref())?;
+        Some(url.replace("&amp;", "&"))
+    }
+}
+
+/// One piece of media attached to a submission.
+#[derive(Debug)]
+pub struct MediaItem<'a> {
+    pub media_id: &'a str,
+    /// Index in the gallery, or `None` for media embedded in the text of the post
+    pub position: Option<usize>,
+    pub caption: Option<&'a str>,
+    pub outbound_url: Option<&'a str>,
+    pub metadata: Option<&'a MediaMetadata>,
+}
+
+/// Gallery items in order, followed by any other media in `media_metadata`.
+pub fn media_items<'a>(
+    gallery_data: Option<&'a GalleryData>,
+    media_metadata: Option<&'a BTreeMap<String, MediaMetadata>>,
+) -> Vec<MediaItem<'a>> {
+    let gallery_items = gallery_data.map_or(&[][..], |gallery| &gallery.items);
+    let mut items: Vec<_> = gallery_items
+        .iter()
+        .enumerate()
+        .map(|(position, item)| MediaItem {
+            media_id: &item.media_id,
+            position: Some(position),
+            caption: item.caption.as_deref(),
+            outbound_url: item.outbound_url.as_deref(),
+            metadata: media_metadata.and_then(|metadata| metadata.get(&item.media_id)),
+        })
+        .collect();
+    for (media_id, metadata) in media_metadata.into_iter().flatten() {
+        if !gallery_items.iter().any(|item| item.media_id == *media_id) {
+            items.push(MediaItem {
+                media_id,
+                position: None,
+                caption: None,
+                outbound_url: None,
+                metadata: Some(metadata),
+            });
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_items() -> serde_json::Result<()> {
+        let gallery_data: GalleryData = serde_json::from_str(
+            r#"{"items": [{"media_id": "b2", "id": 2, "caption": "second upload, shown first"},
+                          {"media_id": "a1", "id": 1, "outbound_url": "https://example.com"}]}"#,
+        )?;
+        let media_metadata: BTreeMap<String, MediaMetadata> = serde_json::from_str(
+            r#"{"a1": {"status": "valid", "e": "Image", "m": "image/png",
+                       "s": {"x": 640, "y": 480, "u": "https://i.redd.it/a1.png?a=1&amp;b=2"}},
+                "b2": {"status": "valid", "e": "AnimatedImage", "m": "image/gif",
+                       "s": {"x": 100, "y": 100, "gif": "https://i.redd.it/b2.gif", "mp4": "https://i.redd.it/b2.mp4"}},
+                "c3": {"status": "failed"}}"#,
+        )?;
+
+        let items = media_items(Some(&gallery_data), Some(&media_metadata));
+        let summary: Vec<_> = items
+            .iter()
+            .map(|item| (item.media_id, item.position))
+            .collect();
+        assert_eq!(summary, [("b2", Some(0)), ("a1", Some(1)), ("c3", None)]);
+
+        let source = items[1].metadata.unwrap().source.as_ref().unwrap();
+        assert_eq!(source.width, Some(640));
+        assert_eq!(
+            source.url().as_deref(),
+            Some("https://i.redd.it/a1.png?a=1&b=2")
+        );
+        assert_eq!(
+            items[0]
+                .metadata
+                .unwrap()
+                .source
+                .as_ref()
+                .unwrap()
+                .url()
+                .as_deref(),
+            Some("https://i.redd.it/b2.gif")
+        );
+        assert!(media_items(None, None).is_empty());
+        Ok(())
+    }
+}
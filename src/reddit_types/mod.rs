@@ -1,10 +1,83 @@
+pub(crate) mod award;
 pub(crate) mod comment;
+pub(crate) mod deletion;
+pub(crate) mod filter_view;
+pub(crate) mod flair;
+pub(crate) mod media;
 pub(crate) mod submission;
+pub(crate) mod subreddit;
 
+use clap::ValueEnum;
 use serde::{Deserialize, Deserializer, de};
 use strum::IntoStaticStr;
 
-#[derive(Deserialize, Debug, Clone, Copy, IntoStaticStr)]
+use crate::reddit_types::{
+    comment::Distinguished,
+    submission::{PostHint, RemovedByCategory, SuggestedSort},
+};
+
+/// Content whose enum fields may hold values the importer does not know about.
+pub trait UnknownVariants {
+    /// The name and value of every field holding an `Other` variant.
+    fn unknown_variants(&self) -> Vec<(&'static str, &str)>;
+}
+
+/// Implements `as_str` for enums with an `Other(String)` variant, returning the value stored verbatim for unknown
+/// variants and the `snake_case` name of known ones.
+macro_rules! impl_as_str {
+    ($($ty:ty),+) => {$(
+        impl $ty {
+            pub(crate) fn as_str(&self) -> &str {
+                match self {
+                    Self::Other(value) => value,
+                    known => known.into(),
+                }
+            }
+
+            pub(crate) fn is_other(&self) -> bool {
+                matches!(self, Self::Other(_))
+            }
+        }
+    )+};
+}
+
+impl_as_str!(
+    SubredditType,
+    Distinguished,
+    SuggestedSort,
+    PostHint,
+    RemovedByCategory
+);
+
+/// How the original JSON line of an item is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RawJsonFormat {
+    /// Store the line as text
+    Plain,
+    /// Store the line as a zstd compressed blob
+    Zstd,
+}
+
+impl RawJsonFormat {
+    pub fn encode(self, line: &str) -> std::io::Result<RawJson> {
+        Ok(match self {
+            RawJsonFormat::Plain => RawJson::Plain(line.to_string()),
+            RawJsonFormat::Zstd => RawJson::Zstd(zstd::bulk::compress(
+                line.as_bytes(),
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?),
+        })
+    }
+}
+
+/// The original JSON line an item was deserialized from.
+#[derive(Debug, Clone)]
+pub enum RawJson {
+    Plain(String),
+    Zstd(Vec<u8>),
+}
+
+#[derive(Deserialize, Debug, Clone, IntoStaticStr)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub(crate) enum SubredditType {
@@ -16,6 +89,9 @@ pub(crate) enum SubredditType {
     GoldOnly,
     Private,
     EmployeesOnly,
+    /// A value not known to the importer, kept verbatim
+    #[serde(untagged)]
+    Other(String),
 }
 
 #[derive(Debug, Clone)]
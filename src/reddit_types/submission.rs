@@ -1,12 +1,20 @@
+use std::{collections::BTreeMap, sync::Arc};
+
 use crate::{
     Filterable,
     deser::{deserialize_optional_time, deserialize_time},
-    reddit_types::{ParentId, SubredditType},
+    reddit_types::{
+        ParentId, RawJson, SubredditType, UnknownVariants,
+        award::Awarding,
+        deletion::DeletionState,
+        flair::{Flair, FlairRichtext},
+        media::{GalleryData, MediaMetadata},
+    },
     storage::{Storable, Storage},
 };
 use anyhow::Result;
 use serde::Deserialize;
-use serde_with::{NoneAsEmptyString, serde_as};
+use serde_with::{DefaultOnNull, NoneAsEmptyString, serde_as};
 use strum::IntoStaticStr;
 use uuid::Uuid;
 
@@ -25,6 +33,13 @@ pub struct Submission {
     #[serde(default)]
     #[serde_as(as = "NoneAsEmptyString")]
     pub author_flair_template_id: Option<Uuid>,
+    pub author_flair_css_class: Option<String>,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub author_flair_richtext: Vec<FlairRichtext>,
+    pub author_flair_background_color: Option<String>,
+    pub author_flair_text_color: Option<String>,
+    pub author_flair_type: Option<String>,
     pub subreddit: Option<String>,
     pub subreddit_id: Option<ParentId>,
     pub id: String,
@@ -35,6 +50,13 @@ pub struct Submission {
     #[serde_as(as = "NoneAsEmptyString")]
     pub link_flair_template_id: Option<Uuid>,
     pub link_flair_text: Option<String>,
+    pub link_flair_css_class: Option<String>,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub link_flair_richtext: Vec<FlairRichtext>,
+    pub link_flair_background_color: Option<String>,
+    pub link_flair_text_color: Option<String>,
+    pub link_flair_type: Option<String>,
     pub spoiler: Option<bool>,
     pub pinned: Option<bool>,
     #[serde(default)]
@@ -56,10 +78,8 @@ pub struct Submission {
     pub is_original_content: Option<bool>,
     #[serde(default)]
     pub is_reddit_media_domain: bool,
-    #[expect(dead_code)]
-    pub gallery_data: Option<serde_json::Value>,
-    #[expect(dead_code)]
-    pub media_metadata: Option<serde_json::Value>,
+    pub gallery_data: Option<GalleryData>,
+    pub media_metadata: Option<BTreeMap<String, MediaMetadata>>,
     #[expect(dead_code)]
     pub url_overridden_by_dest: Option<String>,
     pub quarantine: Option<bool>,
@@ -71,15 +91,31 @@ pub struct Submission {
     pub post_hint: Option<PostHint>,
     pub removed_by_category: Option<RemovedByCategory>,
     pub subreddit_type: Option<SubredditType>,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub all_awardings: Vec<Awarding>,
+    /// Silver, gold and platinum counts keyed by award id, from before `all_awardings` existed
+    #[serde(default)]
+    pub gildings: Option<BTreeMap<String, i64>>,
+    #[serde(default)]
+    pub gilded: Option<i64>,
+    #[serde(default)]
+    pub total_awards_received: Option<i64>,
 
     // Whitelist status stuff
     #[expect(dead_code)]
     pub wls: Option<i8>,
     #[expect(dead_code)]
     pub whitelist_status: Option<String>,
+    /// The line this item was deserialized from, if raw JSON is being kept
+    #[serde(skip)]
+    pub raw_json: Option<RawJson>,
+    /// The input file this item was read from, if revisions are being kept
+    #[serde(skip)]
+    pub source_file: Option<Arc<str>>,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, IntoStaticStr)]
+#[derive(Deserialize, Debug, Clone, IntoStaticStr)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub(crate) enum SuggestedSort {
@@ -91,9 +127,12 @@ pub(crate) enum SuggestedSort {
     Qa,
     Random,
     Top,
+    /// A value not known to the importer, kept verbatim
+    #[serde(untagged)]
+    Other(String),
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, IntoStaticStr)]
+#[derive(Deserialize, Debug, Clone, IntoStaticStr)]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "snake_case")]
 pub(crate) enum PostHint {
@@ -107,9 +146,12 @@ pub(crate) enum PostHint {
     #[serde(rename = "self")]
     Slf,
     Video,
+    /// A value not known to the importer, kept verbatim
+    #[serde(untagged)]
+    Other(String),
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, IntoStaticStr)]
+#[derive(Deserialize, Debug, Clone, IntoStaticStr)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub(crate) enum RemovedByCategory {
@@ -122,6 +164,35 @@ pub(crate) enum RemovedByCategory {
     AntiEvilOps,
     CommunityOps,
     CopyrightTakedown,
+    /// A value not known to the importer, kept verbatim
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl Submission {
+    pub fn author_flair(&self) -> Flair<'_> {
+        Flair {
+            template_id: self.author_flair_template_id,
+            text: self.author_flair_text.as_deref(),
+            css_class: self.author_flair_css_class.as_deref(),
+            richtext: &self.author_flair_richtext,
+            background_color: self.author_flair_background_color.as_deref(),
+            text_color: self.author_flair_text_color.as_deref(),
+            flair_type: self.author_flair_type.as_deref(),
+        }
+    }
+
+    pub fn link_flair(&self) -> Flair<'_> {
+        Flair {
+            template_id: self.link_flair_template_id,
+            text: self.link_flair_text.as_deref(),
+            css_class: self.link_flair_css_class.as_deref(),
+            richtext: &self.link_flair_richtext,
+            background_color: self.link_flair_background_color.as_deref(),
+            text_color: self.link_flair_text_color.as_deref(),
+            flair_type: self.link_flair_type.as_deref(),
+        }
+    }
 }
 
 impl Filterable for Submission {
@@ -137,12 +208,52 @@ impl Filterable for Submission {
     fn created(&self) -> i64 {
         self.created_utc
     }
+    fn deletion_state(&self) -> Option<DeletionState> {
+        Some(DeletionState::classify(
+            self.author.as_deref(),
+            Some(&self.selftext),
+            self.removed_by_category
+                .as_ref()
+                .map(|category| category.as_str()),
+        ))
+    }
+}
+
+impl UnknownVariants for Submission {
+    fn unknown_variants(&self) -> Vec<(&'static str, &str)> {
+        let mut unknown = Vec::new();
+        if let Some(ty) = self.subreddit_type.as_ref().filter(|ty| ty.is_other()) {
+            unknown.push(("subreddit_type", ty.as_str()));
+        }
+        if let Some(sort) = self.suggested_sort.as_ref().filter(|sort| sort.is_other()) {
+            unknown.push(("suggested_sort", sort.as_str()));
+        }
+        if let Some(hint) = self.post_hint.as_ref().filter(|hint| hint.is_other()) {
+            unknown.push(("post_hint", hint.as_str()));
+        }
+        if let Some(category) = self
+            .removed_by_category
+            .as_ref()
+            .filter(|category| category.is_other())
+        {
+            unknown.push(("removed_by_category", category.as_str()));
+        }
+        unknown
+    }
 }
 
 impl Storable for Submission {
     fn store<T: Storage>(self, storage: &mut T) -> Result<usize> {
         storage.insert_submission(self)
     }
+
+    fn set_raw_json(&mut self, raw_json: RawJson) {
+        self.raw_json = Some(raw_json);
+    }
+
+    fn set_source_file(&mut self, source_file: Arc<str>) {
+        self.source_file = Some(source_file);
+    }
 }
 
 #[cfg(test)]
@@ -155,4 +266,21 @@ mod tests {
             let _comment: Submission = serde_json::from_str(line).expect("deserialization");
         }
     }
+
+    #[test]
+    fn test_unknown_variants() {
+        let submissions = include_str!("../../test_data/test_submissions.json");
+        let mut submission: serde_json::Value =
+            serde_json::from_str(submissions.lines().next().unwrap()).expect("json");
+        submission["post_hint"] = "hosted:audio".into();
+        submission["subreddit_type"] = "public".into();
+        let submission: Submission = serde_json::from_value(submission).expect("deserialization");
+
+        assert_eq!(
+            submission.unknown_variants(),
+            vec![("post_hint", "hosted:audio")]
+        );
+        assert_eq!(submission.post_hint.unwrap().as_str(), "hosted:audio");
+        assert_eq!(submission.subreddit_type.unwrap().as_str(), "public");
+    }
 }
@@ -0,0 +1,86 @@
+use crate::{
+    Filterable,
+    deser::{deserialize_optional_time, deserialize_score},
+    reddit_types::{RawJson, SubredditType, UnknownVariants, deletion::DeletionState},
+    storage::{Storable, Storage},
+};
+use anyhow::Result;
+use serde::Deserialize;
+
+/// Subreddit metadata, as published in the subreddit dumps.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Subreddit {
+    /// Base36 id, without the `t5_` prefix
+    pub id: String,
+    pub display_name: String,
+    pub title: Option<String>,
+    pub public_description: Option<String>,
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_score")]
+    pub subscribers: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    pub created_utc: Option<i64>,
+    pub over18: Option<bool>,
+    pub subreddit_type: Option<SubredditType>,
+    pub lang: Option<String>,
+    pub url: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    pub retrieved_on: Option<i64>,
+    /// The line this item was deserialized from, if raw JSON is being kept
+    #[serde(skip)]
+    pub raw_json: Option<RawJson>,
+}
+
+impl Filterable for Subreddit {
+    fn score(&self) -> Option<i64> {
+        None
+    }
+    fn author(&self) -> Option<&str> {
+        None
+    }
+    fn subreddit(&self) -> Option<&str> {
+        Some(self.display_name.as_str())
+    }
+    fn created(&self) -> i64 {
+        self.created_utc.unwrap_or_default()
+    }
+    fn deletion_state(&self) -> Option<DeletionState> {
+        None
+    }
+}
+
+impl UnknownVariants for Subreddit {
+    fn unknown_variants(&self) -> Vec<(&'static str, &str)> {
+        self.subreddit_type
+            .as_ref()
+            .filter(|ty| ty.is_other())
+            .map(|ty| ("subreddit_type", ty.as_str()))
+            .into_iter()
+            .collect()
+    }
+}
+
+impl Storable for Subreddit {
+    // Subreddit metadata names the subreddit in display_name
+    const PRE_FILTER: bool = false;
+
+    fn store<T: Storage>(self, storage: &mut T) -> Result<usize> {
+        storage.insert_subreddit(self)
+    }
+
+    fn set_raw_json(&mut self, raw_json: RawJson) {
+        self.raw_json = Some(raw_json);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_deserialize() {
+        let subreddits = include_str!("../../test_data/test_subreddits.json");
+        for line in subreddits.lines() {
+            let _subreddit: Subreddit = serde_json::from_str(line).expect("deserialization");
+        }
+    }
+}
@@ -0,0 +1,3 @@
+-- Authors interned by the normalised schema. author_id is referenced from comment_fact and submission_fact.
+CREATE TABLE IF NOT EXISTS author (author_id INTEGER PRIMARY KEY,
+                                   name TEXT NOT NULL UNIQUE);
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use ahash::HashSet;
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::{
+    reddit_types::award::{Awarding, award_counts},
+    sqlite::conflict::ChildRows,
+};
+
+/// Writes award definitions and per-item award counts.
+#[derive(Default)]
+pub struct AwardWriter {
+    /// Award ids already written to the award table
+    known_awards: HashSet<String>,
+}
+
+impl AwardWriter {
+    /// Record the awards of one item in `link_table`, which is `comment_award` or `submission_award`, writing the
+    /// counts as `children` says.
+    pub fn insert(
+        &mut self,
+        connection: &Connection,
+        link_table: &str,
+        decoded_reddit_id: i64,
+        all_awardings: &[Awarding],
+        gildings: Option<&BTreeMap<String, i64>>,
+        children: ChildRows,
+    ) -> Result<()> {
+        for awarding in all_awardings {
+            if self.known_awards.contains(&awarding.id) {
+                continue;
+            }
+            connection
+                .prepare_cached(
+                    "INSERT INTO award \
+                     (award_id, name, description, award_type, coin_price, coin_reward, days_of_premium, icon_url, \
+                      decoded_subreddit_id) \
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) \
+                     ON CONFLICT DO NOTHING",
+                )?
+                .execute(rusqlite::params![
+                    awarding.id,
+                    awarding.name,
+                    awarding.description,
+                    awarding.award_type,
+                    awarding.coin_price,
+                    awarding.coin_reward,
+                    awarding.days_of_premium,
+                    awarding.icon_url,
+                    awarding.decoded_subreddit_id(),
+                ])?;
+            self.known_awards.insert(awarding.id.clone());
+        }
+
+        match children {
+            ChildRows::Skip => return Ok(()),
+            ChildRows::Replace => {
+                connection
+                    .prepare_cached(&format!(
+                        "DELETE FROM {link_table} WHERE decoded_reddit_id = ?"
+                    ))?
+                    .execute([decoded_reddit_id])?;
+            }
+            ChildRows::Insert | ChildRows::Merge => (),
+        }
+        let counts = award_counts(all_awardings, gildings);
+        if counts.is_empty() {
+            return Ok(());
+        }
+        let mut stmt = connection.prepare_cached(&format!(
+            "INSERT INTO {link_table} (decoded_reddit_id, award_id, count) VALUES (?, ?, ?) {}",
+            children.clause(
+                link_table,
+                &["decoded_reddit_id", "award_id"],
+                &["decoded_reddit_id", "award_id", "count"]
+            )
+        ))?;
+        for (award_id, count) in counts {
+            stmt.execute(rusqlite::params![decoded_reddit_id, award_id, count])?;
+        }
+        Ok(())
+    }
+}
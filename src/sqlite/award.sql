@@ -0,0 +1,21 @@
+-- Awards given to comments and submissions. award_id is reddit's id for the award: gid_1 to gid_3 for silver, gold and
+-- platinum, and award_<uuid> for later awards. The first definition seen for an award is kept.
+CREATE TABLE IF NOT EXISTS award (award_id TEXT PRIMARY KEY,
+                                  name TEXT,
+                                  description TEXT,
+                                  award_type TEXT,
+                                  coin_price INTEGER,
+                                  coin_reward INTEGER,
+                                  days_of_premium INTEGER,
+                                  icon_url TEXT,
+                                  decoded_subreddit_id INTEGER) WITHOUT ROWID;
+-- The number of times an item received each award. Joins to award on award_id, though awards only known from gildings
+-- have no definition.
+CREATE TABLE IF NOT EXISTS comment_award (decoded_reddit_id INTEGER NOT NULL,
+                                          award_id TEXT NOT NULL,
+                                          count INTEGER NOT NULL,
+                                          PRIMARY KEY (decoded_reddit_id, award_id)) WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS submission_award (decoded_reddit_id INTEGER NOT NULL,
+                                             award_id TEXT NOT NULL,
+                                             count INTEGER NOT NULL,
+                                             PRIMARY KEY (decoded_reddit_id, award_id)) WITHOUT ROWID;
@@ -0,0 +1,392 @@
+//! The columns of the comment and submission tables.
+//!
+//! Each content type has a single registry of the columns it can be stored with. The `CREATE TABLE` and `INSERT`
+//! statements are generated from the columns selected for an import, so adding a field to the database only needs an
+//! entry here.
+
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use rusqlite::{
+    Connection, ToSql,
+    types::{ToSqlOutput, Value as OwnedSqliteValue, ValueRef as RefSqliteValue},
+};
+
+use crate::{
+    Filterable,
+    reddit_types::{comment::Comment, submission::Submission},
+};
+
+/// Which optional columns are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColumnProfile {
+    /// Only the columns most analyses need
+    #[default]
+    Lean,
+    /// Every field the importer models
+    Full,
+}
+
+/// When a column is stored, unless columns are selected explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inclusion {
+    /// Stored even when not selected
+    Always,
+    Lean,
+    Full,
+}
+
+type ValueFn<T> = for<'a> fn(&'a T) -> rusqlite::Result<ToSqlOutput<'a>>;
+
+pub struct Column<T: 'static> {
+    pub name: &'static str,
+    /// The type and constraints of the column in `CREATE TABLE`
+    pub definition: &'static str,
+    pub inclusion: Inclusion,
+    pub value: ValueFn<T>,
+}
+
+impl<T> Column<T> {
+    fn in_profile(&self, profile: ColumnProfile) -> bool {
+        match self.inclusion {
+            Inclusion::Always | Inclusion::Lean => true,
+            Inclusion::Full => profile == ColumnProfile::Full,
+        }
+    }
+}
+
+const fn always<T>(name: &'static str, definition: &'static str, value: ValueFn<T>) -> Column<T> {
+    Column {
+        name,
+        definition,
+        inclusion: Inclusion::Always,
+        value,
+    }
+}
+
+const fn lean<T>(name: &'static str, definition: &'static str, value: ValueFn<T>) -> Column<T> {
+    Column {
+        name,
+        definition,
+        inclusion: Inclusion::Lean,
+        value,
+    }
+}
+
+const fn full<T>(name: &'static str, definition: &'static str, value: ValueFn<T>) -> Column<T> {
+    Column {
+        name,
+        definition,
+        inclusion: Inclusion::Full,
+        value,
+    }
+}
+
+/// A column value as a reference, for writing it somewhere other than Sqlite.
+pub fn value_ref<'a>(output: &'a ToSqlOutput<'a>) -> RefSqliteValue<'a> {
+    match output {
+        ToSqlOutput::Borrowed(value) => *value,
+        ToSqlOutput::Owned(value) => value.into(),
+        _ => RefSqliteValue::Null,
+    }
+}
+
+fn text(value: Option<&str>) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::Borrowed(
+        value.map_or(RefSqliteValue::Null, |value| {
+            RefSqliteValue::Text(value.as_bytes())
+        }),
+    ))
+}
+
+fn integer(value: Option<i64>) -> rusqlite::Result<ToSqlOutput<'static>> {
+    Ok(ToSqlOutput::Owned(OwnedSqliteValue::from(value)))
+}
+
+fn base36(id: &str) -> rusqlite::Result<ToSqlOutput<'static>> {
+    let id = i64::from_str_radix(id, 36)
+        .map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?;
+    integer(Some(id))
+}
+
+// decoded_reddit_id is the decoded base36 reddit_id. We use it as our primary key for performance reasons. See more in
+// https://www.sqlite.org/lang_createtable.html#rowid
+pub static COMMENT_COLUMNS: &[Column<Comment>] = &[
+    always("decoded_reddit_id", "INTEGER PRIMARY KEY", |c| {
+        base36(&c.id)
+    }),
+    always("reddit_id", "TEXT NOT NULL", |c| c.id.to_sql()),
+    lean("permalink", "TEXT", |c| c.permalink.to_sql()),
+    lean("author", "TEXT", |c| c.author.to_sql()),
+    lean("author_premium", "BOOLEAN", |c| c.author_premium.to_sql()),
+    lean("subreddit", "TEXT NOT NULL", |c| c.subreddit.to_sql()),
+    lean("subreddit_type", "TEXT", |c| {
+        text(c.subreddit_type.as_ref().map(|ty| ty.as_str()))
+    }),
+    lean("decoded_subreddit_id", "INTEGER NOT NULL", |c| {
+        c.subreddit_id.decoded_parent_id.to_sql()
+    }),
+    lean("body", "TEXT", |c| c.body.to_sql()),
+    lean("score", "INTEGER", |c| c.score.to_sql()),
+    lean("ups", "INTEGER", |c| c.ups.to_sql()),
+    lean("downs", "INTEGER", |c| c.downs.to_sql()),
+    always("created_utc", "INTEGER NOT NULL", |c| {
+        c.created_utc.to_sql()
+    }),
+    lean("retrieved_on", "INTEGER", |c| c.retrieved_on.to_sql()),
+    lean("edited_utc", "INTEGER", |c| c.edited.to_sql()),
+    lean("parent_type", "INTEGER", |c| {
+        integer(
+            c.parent_id
+                .as_ref()
+                .and_then(|parent_id| parent_id.parent_type)
+                .map(i64::from),
+        )
+    }),
+    lean("parent_id", "TEXT", |c| {
+        text(
+            c.parent_id
+                .as_ref()
+                .map(|parent_id| parent_id.parent_id.as_str()),
+        )
+    }),
+    lean("decoded_parent_id", "INTEGER", |c| {
+        integer(
+            c.parent_id
+                .as_ref()
+                .and_then(|parent_id| i64::from_str_radix(&parent_id.parent_id, 26).ok()),
+        )
+    }),
+    lean("stickied", "BOOLEAN NOT NULL DEFAULT FALSE", |c| {
+        c.stickied.to_sql()
+    }),
+    lean("parent_is_post", "BOOLEAN NOT NULL DEFAULT FALSE", |c| {
+        c.parent_is_post.to_sql()
+    }),
+    lean("is_submitter", "BOOLEAN NOT NULL DEFAULT FALSE", |c| {
+        c.is_submitter.to_sql()
+    }),
+    lean("archived", "BOOLEAN NOT NULL DEFAULT FALSE", |c| {
+        c.archived.to_sql()
+    }),
+    lean("locked", "BOOLEAN NOT NULL DEFAULT FALSE", |c| {
+        c.locked.to_sql()
+    }),
+    lean("collapsed", "BOOLEAN NOT NULL DEFAULT FALSE", |c| {
+        c.collapsed.to_sql()
+    }),
+    lean("distinguished", "TEXT", |c| {
+        text(
+            c.distinguished
+                .as_ref()
+                .map(|distinguished| distinguished.as_str()),
+        )
+    }),
+    lean("gilded", "INTEGER", |c| c.gilded.to_sql()),
+    lean("total_awards_received", "INTEGER", |c| {
+        c.total_awards_received.to_sql()
+    }),
+    lean("author_flair_text", "TEXT", |c| {
+        c.author_flair_text.to_sql()
+    }),
+    lean("author_flair_template_id", "BLOB", |c| {
+        c.author_flair_template_id.to_sql()
+    }),
+    lean("deletion_state", "TEXT", |c| {
+        text(c.deletion_state().map(|state| state.as_str()))
+    }),
+    full("controversiality", "INTEGER", |c| {
+        c.controversiality.to_sql()
+    }),
+    full("score_hidden", "BOOLEAN", |c| c.score_hidden.to_sql()),
+    full("body_sha1", "TEXT", |c| c.body_sha1.to_sql()),
+    full("author_fullname", "TEXT", |c| c.author_fullname.to_sql()),
+    full("updated_on", "INTEGER", |c| c.updated_on.to_sql()),
+    full("author_flair_css_class", "TEXT", |c| {
+        c.author_flair_css_class.to_sql()
+    }),
+];
+
+pub static SUBMISSION_COLUMNS: &[Column<Submission>] = &[
+    always("decoded_reddit_id", "INTEGER PRIMARY KEY", |s| {
+        base36(&s.id)
+    }),
+    always("reddit_id", "TEXT NOT NULL", |s| s.id.to_sql()),
+    lean("author", "TEXT", |s| s.author.to_sql()),
+    lean("author_premium", "BOOLEAN", |s| s.author_premium.to_sql()),
+    lean("title", "TEXT NOT NULL", |s| s.title.to_sql()),
+    lean("author_flair_text", "TEXT", |s| {
+        s.author_flair_text.to_sql()
+    }),
+    lean("author_flair_template_id", "BLOB", |s| {
+        s.author_flair_template_id.to_sql()
+    }),
+    lean("link_flair_template_id", "BLOB", |s| {
+        s.link_flair_template_id.to_sql()
+    }),
+    lean("link_flair_text", "TEXT", |s| s.link_flair_text.to_sql()),
+    lean("subreddit", "TEXT", |s| s.subreddit.to_sql()),
+    lean("decoded_subreddit_id", "INTEGER", |s| {
+        integer(s.subreddit_id.as_ref().map(|id| id.decoded_parent_id))
+    }),
+    lean("subreddit_subscribers", "INTEGER", |s| {
+        s.subreddit_subscribers.to_sql()
+    }),
+    lean("subreddit_type", "TEXT", |s| {
+        text(s.subreddit_type.as_ref().map(|ty| ty.as_str()))
+    }),
+    lean("suggested_sort", "TEXT", |s| {
+        text(s.suggested_sort.as_ref().map(|sort| sort.as_str()))
+    }),
+    lean("decoded_crosspost_parent_id", "INTEGER", |s| {
+        integer(s.crosspost_parent.as_ref().map(|id| id.decoded_parent_id))
+    }),
+    lean("post_hint", "TEXT", |s| {
+        text(s.post_hint.as_ref().map(|hint| hint.as_str()))
+    }),
+    lean("removed_by_category", "TEXT", |s| {
+        text(
+            s.removed_by_category
+                .as_ref()
+                .map(|category| category.as_str()),
+        )
+    }),
+    lean("selftext", "TEXT", |s| s.selftext.to_sql()),
+    lean("permalink", "TEXT", |s| s.permalink.to_sql()),
+    lean("domain", "TEXT", |s| s.domain.to_sql()),
+    lean("url", "TEXT", |s| s.url.to_sql()),
+    lean("score", "INTEGER", |s| s.score.to_sql()),
+    lean("ups", "INTEGER", |s| s.ups.to_sql()),
+    lean("downs", "INTEGER", |s| s.downs.to_sql()),
+    always("created_utc", "INTEGER NOT NULL", |s| {
+        s.created_utc.to_sql()
+    }),
+    lean("edited_utc", "INTEGER", |s| s.edited_utc.to_sql()),
+    lean("retrieved_on", "INTEGER", |s| s.retrieved_on.to_sql()),
+    lean("is_self", "BOOLEAN NOT NULL", |s| s.is_self.to_sql()),
+    lean("over_18", "BOOLEAN NOT NULL", |s| s.over_18.to_sql()),
+    lean("spoiler", "BOOLEAN", |s| s.spoiler.to_sql()),
+    lean("pinned", "BOOLEAN", |s| s.pinned.to_sql()),
+    lean("stickied", "BOOLEAN NOT NULL", |s| s.stickied.to_sql()),
+    lean("is_created_from_ads_ui", "BOOLEAN NOT NULL", |s| {
+        s.is_created_from_ads_ui.to_sql()
+    }),
+    lean("is_gallery", "BOOLEAN NOT NULL", |s| s.is_gallery.to_sql()),
+    lean("is_video", "BOOLEAN NOT NULL", |s| s.is_video.to_sql()),
+    lean("is_original_content", "BOOLEAN", |s| {
+        s.is_original_content.to_sql()
+    }),
+    lean("is_reddit_media_domain", "BOOLEAN NOT NULL", |s| {
+        s.is_reddit_media_domain.to_sql()
+    }),
+    lean("quarantine", "BOOLEAN", |s| s.quarantine.to_sql()),
+    lean("num_comments", "INTEGER NOT NULL", |s| {
+        s.num_comments.to_sql()
+    }),
+    lean("contest_mode", "INTEGER NOT NULL", |s| {
+        s.contest_mode.to_sql()
+    }),
+    lean("num_crossposts", "INTEGER", |s| s.num_crossposts.to_sql()),
+    lean("gilded", "INTEGER", |s| s.gilded.to_sql()),
+    lean("total_awards_received", "INTEGER", |s| {
+        s.total_awards_received.to_sql()
+    }),
+    lean("deletion_state", "TEXT", |s| {
+        text(s.deletion_state().map(|state| state.as_str()))
+    }),
+];
+
+/// Which columns of a table to write.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnSelection {
+    pub profile: ColumnProfile,
+    /// Columns to store instead of those in the profile. Columns stored regardless of selection are added.
+    pub columns: Option<Vec<String>>,
+}
+
+impl ColumnSelection {
+    /// Resolve the selection against `registry`, adding `required` columns and any column `existing` already has.
+    ///
+    /// `existing` holds the columns of the table being written to, so that later imports keep filling columns an
+    /// earlier import created.
+    pub fn resolve<T>(
+        &self,
+        registry: &'static [Column<T>],
+        required: &[&str],
+        existing: &[String],
+    ) -> Result<Vec<&'static Column<T>>> {
+        if let Some(columns) = &self.columns {
+            for name in columns {
+                if !registry.iter().any(|column| column.name == name) {
+                    return Err(anyhow!(
+                        "unknown column {name}, expected one of {}",
+                        registry
+                            .iter()
+                            .map(|column| column.name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(registry
+            .iter()
+            .filter(|column| {
+                column.inclusion == Inclusion::Always
+                    || required.contains(&column.name)
+                    || existing.iter().any(|name| name == column.name)
+                    || self.columns.as_ref().map_or_else(
+                        || column.in_profile(self.profile),
+                        |columns| columns.iter().any(|name| name == column.name),
+                    )
+            })
+            .collect())
+    }
+}
+
+/// The names of the columns `table` has, or nothing if it does not exist.
+pub fn existing_columns(connection: &Connection, table: &str) -> Result<Vec<String>> {
+    Ok(connection
+        .prepare("SELECT name FROM pragma_table_info(?)")?
+        .query_map([table], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() -> Result<()> {
+        let lean = ColumnSelection::default().resolve(COMMENT_COLUMNS, &[], &[])?;
+        assert!(lean.iter().any(|column| column.name == "body"));
+        assert!(!lean.iter().any(|column| column.name == "body_sha1"));
+
+        let selection = ColumnSelection {
+            columns: Some(vec!["body".to_string()]),
+            ..Default::default()
+        };
+        let names: Vec<_> = selection
+            .resolve(COMMENT_COLUMNS, &["author"], &["score".to_string()])?
+            .iter()
+            .map(|column| column.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "decoded_reddit_id",
+                "reddit_id",
+                "author",
+                "body",
+                "score",
+                "created_utc"
+            ]
+        );
+
+        let selection = ColumnSelection {
+            columns: Some(vec!["no_such_column".to_string()]),
+            ..Default::default()
+        };
+        assert!(selection.resolve(COMMENT_COLUMNS, &[], &[]).is_err());
+        Ok(())
+    }
+}
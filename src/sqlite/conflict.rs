@@ -0,0 +1,117 @@
+use clap::ValueEnum;
+
+/// What to do when an imported item is already in the database, such as when importing a newer re-scrape of a dump.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep the row that was imported first
+    #[default]
+    KeepFirst,
+    /// Keep whichever version has the later retrieved_on
+    KeepLatest,
+    /// Overwrite the row with the incoming item
+    Replace,
+    /// Overwrite the row with the fields of the incoming item that are not null
+    MergeNonNull,
+}
+
+impl ConflictPolicy {
+    /// The `ON CONFLICT` clause for inserting `columns` into `table`, whose primary key is decoded_reddit_id.
+    ///
+    /// Updates go through the update triggers, which keep the FTS index in sync.
+    pub fn clause(self, table: &str, columns: &[&str]) -> String {
+        let assignment = |column: &&str| match self {
+            ConflictPolicy::MergeNonNull => {
+                format!("{column} = COALESCE(excluded.{column}, {table}.{column})")
+            }
+            _ => format!("{column} = excluded.{column}"),
+        };
+        let assignments: Vec<_> = columns
+            .iter()
+            .filter(|column| **column != "decoded_reddit_id")
+            .map(assignment)
+            .collect();
+        match self {
+            ConflictPolicy::KeepFirst => "ON CONFLICT DO NOTHING".to_string(),
+            ConflictPolicy::KeepLatest => format!(
+                "ON CONFLICT (decoded_reddit_id) DO UPDATE SET {} \
+                 WHERE excluded.retrieved_on > {table}.retrieved_on \
+                 OR ({table}.retrieved_on IS NULL AND excluded.retrieved_on IS NOT NULL)",
+                assignments.join(", ")
+            ),
+            ConflictPolicy::Replace | ConflictPolicy::MergeNonNull => format!(
+                "ON CONFLICT (decoded_reddit_id) DO UPDATE SET {}",
+                assignments.join(", ")
+            ),
+        }
+    }
+
+    /// How to write the child rows of an incoming item, given the retrieved_on of the stored version of the item, if
+    /// there is one. Only keep-latest looks at the stored version.
+    pub fn child_rows(self, retrieved_on: Option<i64>, stored: Option<Option<i64>>) -> ChildRows {
+        match self {
+            ConflictPolicy::KeepFirst => ChildRows::Insert,
+            ConflictPolicy::KeepLatest => match stored {
+                Some(stored) if !is_later(retrieved_on, stored) => ChildRows::Skip,
+                _ => ChildRows::Replace,
+            },
+            ConflictPolicy::Replace => ChildRows::Replace,
+            ConflictPolicy::MergeNonNull => ChildRows::Merge,
+        }
+    }
+
+    /// Columns the policy compares, which must be stored.
+    pub fn required_columns(self) -> &'static [&'static str] {
+        match self {
+            ConflictPolicy::KeepLatest => &["retrieved_on"],
+            _ => &[],
+        }
+    }
+}
+
+/// Whether an incoming item replaces the stored version under keep-latest, as the `ON CONFLICT` clause decides.
+pub fn is_later(retrieved_on: Option<i64>, stored: Option<i64>) -> bool {
+    match (retrieved_on, stored) {
+        (Some(retrieved_on), Some(stored)) => retrieved_on > stored,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// How the rows an item has in child tables, such as its award counts, media and flair templates, are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildRows {
+    /// Add rows that are not stored yet, keeping the rest
+    Insert,
+    /// Replace the rows of the item with the incoming ones
+    Replace,
+    /// Overwrite stored rows with the fields of the incoming ones that are not null
+    Merge,
+    /// Leave the rows as they are, as the stored version of the item is kept
+    Skip,
+}
+
+impl ChildRows {
+    /// The `ON CONFLICT` clause for inserting `columns` into `table`, whose primary key is `key`.
+    ///
+    /// Rows of the item that are not in the incoming version are only removed by deleting them first.
+    pub fn clause(self, table: &str, key: &[&str], columns: &[&str]) -> String {
+        let assignments: Vec<_> = columns
+            .iter()
+            .filter(|column| !key.contains(column))
+            .map(|column| match self {
+                ChildRows::Merge => {
+                    format!("{column} = COALESCE(excluded.{column}, {table}.{column})")
+                }
+                _ => format!("{column} = excluded.{column}"),
+            })
+            .collect();
+        match self {
+            ChildRows::Insert | ChildRows::Skip => "ON CONFLICT DO NOTHING".to_string(),
+            ChildRows::Replace | ChildRows::Merge => format!(
+                "ON CONFLICT ({}) DO UPDATE SET {}",
+                key.join(", "),
+                assignments.join(", ")
+            ),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Flair templates seen on comments and submissions.
+//!
+//! Only flair with a `template_id` is written to the flair tables. Flair without one has no key to store it by, and its
+//! text is kept on the item in the `author_flair_text` and `link_flair_text` columns of the lean profile.
+
+use ahash::HashSet;
+use anyhow::Result;
+use rusqlite::Connection;
+use uuid::Uuid;
+
+use crate::{
+    reddit_types::flair::Flair,
+    sqlite::{conflict::ChildRows, placeholders},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum FlairKind {
+    Author,
+    Link,
+}
+
+/// Writes flair templates to the author_flair and link_flair tables.
+#[derive(Default)]
+pub struct FlairWriter {
+    /// Template ids already written, by kind
+    known_author_flair: HashSet<Uuid>,
+    known_link_flair: HashSet<Uuid>,
+}
+
+const COLUMNS: &[&str] = &[
+    "template_id",
+    "decoded_subreddit_id",
+    "text",
+    "css_class",
+    "richtext",
+    "background_color",
+    "text_color",
+    "flair_type",
+];
+
+impl FlairWriter {
+    /// Record the template of `flair`, if it has one, writing it as `children` says. Under keep-first each template
+    /// is only written once.
+    pub fn insert(
+        &mut self,
+        connection: &Connection,
+        kind: FlairKind,
+        decoded_subreddit_id: Option<i64>,
+        flair: Flair<'_>,
+        children: ChildRows,
+    ) -> Result<()> {
+        // Flair without a template is only stored as the text columns of the item
+        let Some(template_id) = flair.template_id else {
+            return Ok(());
+        };
+        let (known, table) = match kind {
+            FlairKind::Author => (&mut self.known_author_flair, "author_flair"),
+            FlairKind::Link => (&mut self.known_link_flair, "link_flair"),
+        };
+        match children {
+            ChildRows::Skip => return Ok(()),
+            ChildRows::Insert if !known.insert(template_id) => return Ok(()),
+            _ => (),
+        }
+        let richtext = if flair.richtext.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(flair.richtext)?)
+        };
+        let sql = format!(
+            "INSERT INTO {table} ({}) VALUES {} {}",
+            COLUMNS.join(", "),
+            placeholders(COLUMNS.len()),
+            children.clause(table, &["template_id"], COLUMNS)
+        );
+        connection.prepare_cached(&sql)?.execute(rusqlite::params![
+            template_id,
+            decoded_subreddit_id,
+            flair.text,
+            flair.css_class,
+            richtext,
+            flair.background_color,
+            flair.text_color,
+            flair.flair_type,
+        ])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+-- Flair templates seen on comments and submissions, keyed by template id. Author flair is shown next to a user's name
+-- and link flair on a submission; items refer to them through their author_flair_template_id and link_flair_template_id
+-- columns. The first version of a template seen is kept, unless --on-conflict updates it from a later item. richtext is
+-- a JSON array of text and emoji parts.
+CREATE TABLE IF NOT EXISTS author_flair (template_id BLOB PRIMARY KEY,
+                                         decoded_subreddit_id INTEGER,
+                                         text TEXT,
+                                         css_class TEXT,
+                                         richtext TEXT,
+                                         background_color TEXT,
+                                         text_color TEXT,
+                                         flair_type TEXT);
+CREATE TABLE IF NOT EXISTS link_flair (template_id BLOB PRIMARY KEY,
+                                       decoded_subreddit_id INTEGER,
+                                       text TEXT,
+                                       css_class TEXT,
+                                       richtext TEXT,
+                                       background_color TEXT,
+                                       text_color TEXT,
+                                       flair_type TEXT);
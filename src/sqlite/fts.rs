@@ -0,0 +1,564 @@
+//! Full text search indexes over comments and submissions.
+//!
+//! The FTS5 tables are external content tables, reading the text from the comment and submission tables, and are
+//! kept in sync with them by triggers. Both are generated from an [`FtsIndex`], which chooses the tokenizer and the
+//! columns that are indexed.
+
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use log::{info, warn};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::sqlite::columns::{COMMENT_COLUMNS, Column, SUBMISSION_COLUMNS, existing_columns};
+
+/// The content tables with an index.
+const TABLES: [&str; 2] = ["comment", "submission"];
+
+/// Columns every FTS table has, so that matches can be shown with their author and subreddit. They are only indexed
+/// when selected.
+const DISPLAY_COLUMNS: [&str; 2] = ["author", "subreddit"];
+
+/// When the FTS indexes are written during an import.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FtsBuild {
+    /// Index each row as it is inserted, using triggers
+    #[default]
+    Triggers,
+    /// Load the tables without triggers, then build the indexes in one pass at the end of the import, which is much
+    /// faster. The triggers are installed afterwards so later changes are indexed.
+    PostLoad,
+}
+
+/// How text is split into the tokens that are indexed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FtsTokenizer {
+    /// Split on unicode word boundaries and fold case
+    #[default]
+    Unicode61,
+    /// unicode61 followed by porter stemming, so that a search for "run" also matches "running"
+    Porter,
+    /// Index every sequence of three characters, for substring search with MATCH, LIKE and GLOB. Makes a much larger
+    /// index.
+    Trigram,
+}
+
+/// The tokenizer and indexed columns of the FTS tables.
+#[derive(Debug, Clone)]
+pub struct FtsIndex {
+    pub tokenizer: FtsTokenizer,
+    /// The remove_diacritics option of the tokenizer. 0 keeps diacritics, 1 and 2 remove them, 2 also from code
+    /// points composed with several diacritics. Trigram only supports 0 and 1.
+    pub remove_diacritics: Option<u8>,
+    pub comment_columns: Vec<String>,
+    pub submission_columns: Vec<String>,
+}
+
+/// Settings chosen for an index. Each one that is given replaces that of the existing index, or of the default index
+/// when there is none, so that an index built earlier is not changed by accident.
+#[derive(Debug, Clone, Default)]
+pub struct FtsIndexOptions {
+    pub tokenizer: Option<FtsTokenizer>,
+    pub remove_diacritics: Option<u8>,
+    pub comment_columns: Option<Vec<String>>,
+    pub submission_columns: Option<Vec<String>>,
+}
+
+impl FtsIndexOptions {
+    /// `base` with the settings that were given replaced.
+    pub fn apply(&self, base: FtsIndex) -> FtsIndex {
+        FtsIndex {
+            tokenizer: self.tokenizer.unwrap_or(base.tokenizer),
+            remove_diacritics: self.remove_diacritics.or(base.remove_diacritics),
+            comment_columns: self.comment_columns.clone().unwrap_or(base.comment_columns),
+            submission_columns: self
+                .submission_columns
+                .clone()
+                .unwrap_or(base.submission_columns),
+        }
+    }
+
+    /// The index these options choose for the database of `connection`.
+    pub fn resolve(&self, connection: &Connection) -> Result<FtsIndex> {
+        Ok(self.apply(FtsIndex::existing(connection)?.unwrap_or_default()))
+    }
+}
+
+impl Default for FtsIndex {
+    fn default() -> Self {
+        FtsIndex {
+            tokenizer: FtsTokenizer::default(),
+            remove_diacritics: None,
+            comment_columns: vec!["body".to_string()],
+            submission_columns: vec!["title".to_string(), "selftext".to_string()],
+        }
+    }
+}
+
+impl FtsIndex {
+    /// Check that the tokenizer supports its options, and that every indexed column is a text column of its table.
+    pub fn validate(&self) -> Result<()> {
+        if self.tokenizer == FtsTokenizer::Trigram && self.remove_diacritics == Some(2) {
+            return Err(anyhow!(
+                "the trigram tokenizer only supports remove_diacritics 0 or 1"
+            ));
+        }
+        validate_columns(COMMENT_COLUMNS, &self.comment_columns, "comment")?;
+        validate_columns(SUBMISSION_COLUMNS, &self.submission_columns, "submission")
+    }
+
+    /// The index the database already has, read from the definitions of its FTS tables, or `None` if it has none.
+    pub fn existing(connection: &Connection) -> Result<Option<FtsIndex>> {
+        let mut existing: Option<FtsIndex> = None;
+        for table in TABLES {
+            let Some(definition) = read_definition(connection, table)? else {
+                continue;
+            };
+            let index = existing.get_or_insert_with(|| FtsIndex {
+                tokenizer: definition.tokenizer,
+                remove_diacritics: definition.remove_diacritics,
+                ..Default::default()
+            });
+            match table {
+                "comment" => index.comment_columns = definition.indexed,
+                _ => index.submission_columns = definition.indexed,
+            }
+        }
+        Ok(existing)
+    }
+
+    /// Whether the FTS table over `table` described by `definition` was built with these settings.
+    fn matches(&self, table: &str, definition: &Definition) -> bool {
+        let mut indexed: Vec<&str> = self.indexed(table).iter().map(String::as_str).collect();
+        let mut existing: Vec<&str> = definition.indexed.iter().map(String::as_str).collect();
+        indexed.sort_unstable();
+        existing.sort_unstable();
+        self.tokenizer == definition.tokenizer
+            && self.remove_diacritics == definition.remove_diacritics
+            && indexed == existing
+    }
+
+    fn indexed(&self, table: &str) -> &[String] {
+        match table {
+            "comment" => &self.comment_columns,
+            _ => &self.submission_columns,
+        }
+    }
+
+    /// The columns of the FTS table over `table`, all of which must be stored in `table`.
+    pub fn columns(&self, table: &str) -> Vec<&str> {
+        let mut columns = DISPLAY_COLUMNS.to_vec();
+        for column in self.indexed(table) {
+            if !columns.contains(&column.as_str()) {
+                columns.push(column);
+            }
+        }
+        columns
+    }
+
+    fn tokenize(&self) -> String {
+        let tokenizer = match self.tokenizer {
+            FtsTokenizer::Unicode61 => "unicode61",
+            FtsTokenizer::Porter => "porter unicode61",
+            FtsTokenizer::Trigram => "trigram",
+        };
+        match self.remove_diacritics {
+            Some(remove_diacritics) => format!("{tokenizer} remove_diacritics {remove_diacritics}"),
+            None => tokenizer.to_string(),
+        }
+    }
+
+    fn create_table(&self, table: &str) -> String {
+        let indexed = self.indexed(table);
+        let columns: Vec<String> = self
+            .columns(table)
+            .into_iter()
+            .map(|column| {
+                if indexed.iter().any(|name| name == column) {
+                    column.to_string()
+                } else {
+                    format!("{column} UNINDEXED")
+                }
+            })
+            .collect();
+        format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {table}_fts USING fts5({}, tokenize = '{}', content = '{table}', \
+             content_rowid = 'rowid');",
+            columns.join(", "),
+            self.tokenize()
+        )
+    }
+}
+
+/// The settings of an FTS table, as written by [`FtsIndex::create_table`].
+struct Definition {
+    tokenizer: FtsTokenizer,
+    remove_diacritics: Option<u8>,
+    indexed: Vec<String>,
+}
+
+/// The definition of the FTS table over `table`, or `None` if there is none.
+fn read_definition(connection: &Connection, table: &str) -> Result<Option<Definition>> {
+    let sql: Option<String> = connection
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+            [format!("{table}_fts")],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(sql) = sql else {
+        return Ok(None);
+    };
+    let definition = parse_definition(&sql)
+        .ok_or_else(|| anyhow!("unable to read the definition of {table}_fts: {sql}"))?;
+    Ok(Some(definition))
+}
+
+fn parse_definition(sql: &str) -> Option<Definition> {
+    let arguments = sql.split_once("fts5(")?.1.strip_suffix(')')?;
+    let mut tokenize = None;
+    let mut indexed = Vec::new();
+    for argument in arguments.split(',').map(str::trim) {
+        if let Some(value) = argument.strip_prefix("tokenize") {
+            tokenize = Some(
+                value
+                    .trim_start()
+                    .strip_prefix('=')?
+                    .trim()
+                    .trim_matches('\''),
+            );
+        } else if !argument.contains('=') && !argument.ends_with("UNINDEXED") {
+            indexed.push(argument.to_string());
+        }
+    }
+    // Indexes created by earlier versions leave the tokenizer to FTS5, which defaults to unicode61
+    let mut words = tokenize.unwrap_or("unicode61").split_whitespace();
+    let tokenizer = match words.next()? {
+        "unicode61" => FtsTokenizer::Unicode61,
+        "porter" => FtsTokenizer::Porter,
+        "trigram" => FtsTokenizer::Trigram,
+        _ => return None,
+    };
+    let mut remove_diacritics = None;
+    while let Some(word) = words.next() {
+        if word == "remove_diacritics" {
+            remove_diacritics = Some(words.next()?.parse().ok()?);
+        }
+    }
+    Some(Definition {
+        tokenizer,
+        remove_diacritics,
+        indexed,
+    })
+}
+
+fn validate_columns<T>(registry: &[Column<T>], columns: &[String], table: &str) -> Result<()> {
+    let text_columns: Vec<_> = registry
+        .iter()
+        .filter(|column| column.definition.starts_with("TEXT"))
+        .map(|column| column.name)
+        .collect();
+    if columns.is_empty() {
+        return Err(anyhow!("no {table} columns selected for full text search"));
+    }
+    match columns
+        .iter()
+        .find(|column| !text_columns.contains(&column.as_str()))
+    {
+        Some(column) => Err(anyhow!(
+            "{column} is not a text column of {table}, expected one of {}",
+            text_columns.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The triggers keeping the FTS table over `table`, with `columns`, in sync with it.
+///
+/// Existing triggers are replaced.
+fn triggers(table: &str, columns: &[String]) -> String {
+    let names = columns.join(", ");
+    let prefixed = |prefix: &str| {
+        columns
+            .iter()
+            .map(|column| format!("{prefix}.{column}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let (new, old) = (prefixed("new"), prefixed("old"));
+    format!(
+        "DROP TRIGGER IF EXISTS {table}_ai;
+         DROP TRIGGER IF EXISTS {table}_ad;
+         DROP TRIGGER IF EXISTS {table}_au;
+         CREATE TRIGGER {table}_ai AFTER INSERT ON {table}
+             BEGIN
+                 INSERT INTO {table}_fts (rowid, {names}) VALUES (new.rowid, {new});
+             END;
+         CREATE TRIGGER {table}_ad AFTER DELETE ON {table}
+             BEGIN
+                 INSERT INTO {table}_fts ({table}_fts, rowid, {names}) VALUES ('delete', old.rowid, {old});
+             END;
+         CREATE TRIGGER {table}_au AFTER UPDATE ON {table}
+             BEGIN
+                 INSERT INTO {table}_fts ({table}_fts, rowid, {names}) VALUES ('delete', old.rowid, {old});
+                 INSERT INTO {table}_fts (rowid, {names}) VALUES (new.rowid, {new});
+             END;"
+    )
+}
+
+/// Whether `table` exists, failing if it is one of the views of the normalised schema.
+fn is_content_table(connection: &Connection, table: &str) -> Result<bool> {
+    let kind: Option<String> = connection
+        .query_row(
+            "SELECT type FROM sqlite_master WHERE name = ?",
+            [table],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match kind.as_deref() {
+        Some("table") => Ok(true),
+        Some(_) => Err(anyhow!(
+            "full text search is not supported with the normalised schema"
+        )),
+        None => Ok(false),
+    }
+}
+
+/// Create the FTS tables and the triggers that keep them in sync with the content tables.
+///
+/// Existing FTS tables are kept as they are, as changing their tokenizer or columns needs a rebuild. A warning is logged
+/// when they were built with other settings than `index`.
+pub fn setup_fts(connection: &Connection, index: &FtsIndex) -> Result<()> {
+    for table in TABLES {
+        if !is_content_table(connection, table)? {
+            continue;
+        }
+        connection.execute_batch(&index.create_table(table))?;
+        // The triggers must match the columns the table has, which may have been chosen by an earlier import
+        let columns = existing_columns(connection, &format!("{table}_fts"))?;
+        let matches = read_definition(connection, table)?
+            .is_some_and(|definition| index.matches(table, &definition));
+        if !matches || columns != index.columns(table) {
+            warn!(table, tokenize = index.tokenize(), indexed:? = index.indexed(table);
+                  "The full text search index has different settings, run fts rebuild to change them");
+        }
+        connection.execute_batch(&triggers(table, &columns))?;
+    }
+    Ok(())
+}
+
+/// Remove the triggers, so that rows are loaded without being indexed until [`rebuild`] is run.
+pub fn drop_fts_triggers(connection: &Connection) -> Result<()> {
+    for table in TABLES {
+        connection.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS {table}_ai;
+             DROP TRIGGER IF EXISTS {table}_ad;
+             DROP TRIGGER IF EXISTS {table}_au;"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Regenerate the triggers of tables that have them from the columns of their FTS table, and index the content tables
+/// again with the existing settings, as earlier triggers indexed the wrong column.
+pub fn repair_fts(connection: &Connection) -> Result<()> {
+    for table in TABLES {
+        let columns = existing_columns(connection, &format!("{table}_fts"))?;
+        if columns.is_empty() || !is_content_table(connection, table)? {
+            continue;
+        }
+        let has_trigger: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'trigger' AND name = ?)",
+            [format!("{table}_au")],
+            |row| row.get(0),
+        )?;
+        if has_trigger {
+            connection.execute_batch(&triggers(table, &columns))?;
+        }
+        info!(table; "Rebuilding full text search index");
+        connection.execute_batch(&format!(
+            "INSERT INTO {table}_fts ({table}_fts) VALUES ('rebuild');"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Build the FTS indexes of the database at `filename` from its content tables, replacing any existing index.
+pub fn rebuild_fts(filename: &Path, options: &FtsIndexOptions) -> Result<()> {
+    let connection = Connection::open(filename)?;
+    let index = options.resolve(&connection)?;
+    index.validate()?;
+    rebuild(&connection, &index)
+}
+
+/// Recreate the FTS tables and triggers, then index every row of the content tables and merge the index into as few
+/// segments as possible.
+pub fn rebuild(connection: &Connection, index: &FtsIndex) -> Result<()> {
+    for table in TABLES {
+        if !is_content_table(connection, table)? {
+            continue;
+        }
+        info!(table; "Rebuilding full text search index");
+        let columns: Vec<String> = index.columns(table).into_iter().map(String::from).collect();
+        connection.execute_batch(&format!("DROP TABLE IF EXISTS {table}_fts;"))?;
+        connection.execute_batch(&index.create_table(table))?;
+        connection.execute_batch(&triggers(table, &columns))?;
+        connection.execute_batch(&format!(
+            "INSERT INTO {table}_fts ({table}_fts) VALUES ('rebuild');
+             INSERT INTO {table}_fts ({table}_fts) VALUES ('optimize');"
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(connection: &Connection, sql: &str) -> Result<i64> {
+        Ok(connection.query_row(sql, [], |row| row.get(0))?)
+    }
+
+    #[test]
+    fn test_rebuild() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(
+            "CREATE TABLE comment (decoded_reddit_id INTEGER PRIMARY KEY, author TEXT, subreddit TEXT, body TEXT);
+             INSERT INTO comment VALUES (1, 'snoo', 'pics', 'hello world'), (2, 'snoo', 'pics', 'goodbye');",
+        )?;
+        // Rows written before the index existed are only indexed by a rebuild
+        rebuild(&connection, &FtsIndex::default())?;
+        connection.execute(
+            "INSERT INTO comment VALUES (3, 'snoo', 'pics', 'hello again')",
+            [],
+        )?;
+        assert_eq!(
+            count(
+                &connection,
+                "SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH 'hello'"
+            )?,
+            2
+        );
+        // No submission table, so no submission index
+        assert_eq!(
+            count(
+                &connection,
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'submission_fts'"
+            )?,
+            0
+        );
+
+        connection.execute_batch("DROP TABLE comment; CREATE VIEW comment AS SELECT 1;")?;
+        assert!(rebuild(&connection, &FtsIndex::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_existing() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        assert!(FtsIndex::existing(&connection)?.is_none());
+        connection.execute_batch(
+            "CREATE TABLE comment (decoded_reddit_id INTEGER PRIMARY KEY, author TEXT, subreddit TEXT, body TEXT);
+             CREATE TABLE submission (decoded_reddit_id INTEGER PRIMARY KEY, author TEXT, subreddit TEXT, title TEXT,
+                                      selftext TEXT);",
+        )?;
+        let index = FtsIndex {
+            tokenizer: FtsTokenizer::Porter,
+            remove_diacritics: Some(2),
+            comment_columns: vec!["author".to_string(), "body".to_string()],
+            submission_columns: vec!["title".to_string()],
+        };
+        rebuild(&connection, &index)?;
+        let existing = FtsIndex::existing(&connection)?.expect("an existing index");
+        assert_eq!(existing.tokenizer, index.tokenizer);
+        assert_eq!(existing.remove_diacritics, index.remove_diacritics);
+        assert_eq!(existing.comment_columns, index.comment_columns);
+        assert_eq!(existing.submission_columns, index.submission_columns);
+
+        connection.execute_batch(
+            "DROP TABLE comment_fts;
+             DROP TABLE submission_fts;
+             CREATE VIRTUAL TABLE comment_fts USING fts5(author UNINDEXED, subreddit UNINDEXED, body,
+                                                         content = 'comment', content_rowid = 'rowid');",
+        )?;
+        let existing = FtsIndex::existing(&connection)?.expect("an existing index");
+        assert_eq!(existing.tokenizer, FtsTokenizer::Unicode61);
+        assert_eq!(existing.remove_diacritics, None);
+        assert_eq!(existing.comment_columns, ["body"]);
+        assert_eq!(existing.submission_columns, ["title", "selftext"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_options() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(
+            "CREATE TABLE comment (decoded_reddit_id INTEGER PRIMARY KEY, author TEXT, subreddit TEXT, body TEXT);
+             INSERT INTO comment VALUES (1, 'snoo', 'pics', 'Running in the café');",
+        )?;
+        let porter = FtsIndex {
+            tokenizer: FtsTokenizer::Porter,
+            remove_diacritics: Some(2),
+            comment_columns: vec!["author".to_string(), "body".to_string()],
+            ..Default::default()
+        };
+        porter.validate()?;
+        rebuild(&connection, &porter)?;
+        assert_eq!(
+            count(
+                &connection,
+                "SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH 'run AND cafe AND author:snoo'"
+            )?,
+            1
+        );
+
+        let trigram = FtsIndex {
+            tokenizer: FtsTokenizer::Trigram,
+            ..Default::default()
+        };
+        rebuild(&connection, &trigram)?;
+        assert_eq!(
+            count(
+                &connection,
+                "SELECT COUNT(*) FROM comment_fts WHERE body LIKE '%unnin%'"
+            )?,
+            1
+        );
+        // Any other setting is a different index, even with the same columns
+        let definition = read_definition(&connection, "comment")?.expect("a comment index");
+        assert!(trigram.matches("comment", &definition));
+        let diacritics = FtsIndex {
+            remove_diacritics: Some(0),
+            ..trigram.clone()
+        };
+        assert!(!diacritics.matches("comment", &definition));
+        assert!(!porter.matches("comment", &definition));
+        // Setting up with other options keeps the index, and triggers for the columns it has
+        setup_fts(&connection, &porter)?;
+        connection.execute(
+            "UPDATE comment SET body = 'walking' WHERE decoded_reddit_id = 1",
+            [],
+        )?;
+        assert_eq!(
+            count(
+                &connection,
+                "SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH 'alki'"
+            )?,
+            1
+        );
+
+        let invalid = FtsIndex {
+            comment_columns: vec!["score".to_string()],
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+        let invalid = FtsIndex {
+            tokenizer: FtsTokenizer::Trigram,
+            remove_diacritics: Some(2),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+-- Input files hashed before import. sha256 is the digest of the file as read, expected_sha256 the digest listed in the
+-- checksum manifest (if any).
+CREATE TABLE IF NOT EXISTS input_file (path TEXT PRIMARY KEY,
+                                       size INTEGER NOT NULL,
+                                       sha256 TEXT NOT NULL,
+                                       expected_sha256 TEXT,
+                                       verified BOOLEAN NOT NULL,
+                                       verified_utc INTEGER NOT NULL);
@@ -0,0 +1,92 @@
+use ahash::{HashMap, HashSet};
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension};
+
+/// Maps author and subreddit names to the ids of their rows in the `author` and `subreddit` tables.
+///
+/// Every name seen during an import is cached, so each one costs a database lookup only the first time it is seen.
+#[derive(Default)]
+pub struct Interner {
+    authors: HashMap<String, i64>,
+    subreddits: HashMap<String, i64>,
+    /// Subreddit ids known to have a row in the subreddit table
+    subreddit_ids: HashSet<i64>,
+}
+
+impl Interner {
+    pub fn author_id(&mut self, connection: &Connection, name: &str) -> Result<i64> {
+        if let Some(id) = self.authors.get(name) {
+            return Ok(*id);
+        }
+        // The no-op update makes RETURNING produce the existing row's id on conflict
+        let id = connection
+            .prepare_cached(
+                "INSERT INTO author (name) VALUES (?) \
+                 ON CONFLICT (name) DO UPDATE SET name = excluded.name \
+                 RETURNING author_id",
+            )?
+            .query_row([name], |row| row.get(0))?;
+        self.authors.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    /// The `decoded_subreddit_id` to store for content in subreddit `name`.
+    ///
+    /// Content without a subreddit id is matched to a subreddit by name, and gets a negative id if no subreddit with
+    /// that name is known.
+    pub fn subreddit_id(
+        &mut self,
+        connection: &Connection,
+        name: Option<&str>,
+        decoded_subreddit_id: Option<i64>,
+    ) -> Result<Option<i64>> {
+        match (name, decoded_subreddit_id) {
+            (Some(name), Some(id)) => {
+                if self.subreddit_ids.insert(id) {
+                    connection
+                        .prepare_cached(
+                            "INSERT INTO subreddit (decoded_subreddit_id, display_name) VALUES (?, ?) \
+                             ON CONFLICT DO NOTHING",
+                        )?
+                        .execute(rusqlite::params![id, name])?;
+                }
+                if !matches!(self.subreddits.get(name), Some(known) if *known >= 0) {
+                    self.subreddits.insert(name.to_string(), id);
+                }
+                Ok(Some(id))
+            }
+            (Some(name), None) => {
+                if let Some(id) = self.subreddits.get(name) {
+                    return Ok(Some(*id));
+                }
+                let existing: Option<i64> = connection
+                    .prepare_cached(
+                        "SELECT decoded_subreddit_id FROM subreddit WHERE display_name = ? \
+                         ORDER BY decoded_subreddit_id DESC LIMIT 1",
+                    )?
+                    .query_row([name], |row| row.get(0))
+                    .optional()?;
+                let id = match existing {
+                    Some(id) => id,
+                    None => {
+                        let id: i64 = connection.query_row(
+                            "SELECT MIN(COALESCE(MIN(decoded_subreddit_id), 0), 0) - 1 FROM subreddit",
+                            [],
+                            |row| row.get(0),
+                        )?;
+                        connection.execute(
+                            "INSERT INTO subreddit (decoded_subreddit_id, display_name) VALUES (?, ?)",
+                            rusqlite::params![id, name],
+                        )?;
+                        id
+                    }
+                };
+                self.subreddit_ids.insert(id);
+                self.subreddits.insert(name.to_string(), id);
+                Ok(Some(id))
+            }
+            // Without a name there is nothing to put in the subreddit table
+            (None, id) => Ok(id),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::{
+    reddit_types::{media::media_items, submission::Submission},
+    sqlite::{conflict::ChildRows, placeholders},
+};
+
+const COLUMNS: &[&str] = &[
+    "decoded_reddit_id",
+    "media_id",
+    "position",
+    "caption",
+    "outbound_url",
+    "status",
+    "media_type",
+    "mime_type",
+    "width",
+    "height",
+    "url",
+];
+
+/// Record the gallery items and embedded media of a submission in submission_media, writing them as `children` says.
+pub fn insert_media(
+    connection: &Connection,
+    decoded_reddit_id: i64,
+    submission: &Submission,
+    children: ChildRows,
+) -> Result<()> {
+    match children {
+        ChildRows::Skip => return Ok(()),
+        ChildRows::Replace => {
+            connection
+                .prepare_cached("DELETE FROM submission_media WHERE decoded_reddit_id = ?")?
+                .execute([decoded_reddit_id])?;
+        }
+        ChildRows::Insert | ChildRows::Merge => (),
+    }
+    let items = media_items(
+        submission.gallery_data.as_ref(),
+        submission.media_metadata.as_ref(),
+    );
+    if items.is_empty() {
+        return Ok(());
+    }
+    let mut stmt = connection.prepare_cached(&format!(
+        "INSERT INTO submission_media ({}) VALUES {} {}",
+        COLUMNS.join(", "),
+        placeholders(COLUMNS.len()),
+        children.clause(
+            "submission_media",
+            &["decoded_reddit_id", "media_id"],
+            COLUMNS
+        )
+    ))?;
+    for item in items {
+        let metadata = item.metadata;
+        let source = metadata.and_then(|metadata| metadata.source.as_ref());
+        stmt.execute(rusqlite::params![
+            decoded_reddit_id,
+            item.media_id,
+            item.position,
+            item.caption,
+            item.outbound_url,
+            metadata.and_then(|metadata| metadata.status.as_deref()),
+            metadata.and_then(|metadata| metadata.kind.as_deref()),
+            metadata.and_then(|metadata| metadata.mime_type.as_deref()),
+            source.and_then(|source| source.width),
+            source.and_then(|source| source.height),
+            source.and_then(|source| source.url()),
+        ])?;
+    }
+    Ok(())
+}
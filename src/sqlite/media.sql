@@ -0,0 +1,14 @@
+-- Media attached to submissions, from gallery_data and media_metadata. position is the index of the item in a gallery,
+-- and NULL for media embedded in the text of a post.
+CREATE TABLE IF NOT EXISTS submission_media (decoded_reddit_id INTEGER NOT NULL,
+                                             media_id TEXT NOT NULL,
+                                             position INTEGER,
+                                             caption TEXT,
+                                             outbound_url TEXT,
+                                             status TEXT,
+                                             media_type TEXT,
+                                             mime_type TEXT,
+                                             width INTEGER,
+                                             height INTEGER,
+                                             url TEXT,
+                                             PRIMARY KEY (decoded_reddit_id, media_id)) WITHOUT ROWID;
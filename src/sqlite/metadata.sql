@@ -0,0 +1,5 @@
+-- One row per recorded fact about each run of the importer. Lists and objects are stored as JSON.
+CREATE TABLE IF NOT EXISTS metadata (import_id INTEGER NOT NULL,
+                                     key TEXT NOT NULL,
+                                     value TEXT,
+                                     PRIMARY KEY (import_id, key)) WITHOUT ROWID;
@@ -0,0 +1,179 @@
+//! Upgrades databases created by earlier versions of the importer.
+//!
+//! The version of a database is the highest version in its schema_version table. Databases created before the table
+//! was introduced are version 0, and may have the layout of any earlier version, so each migration checks whether it
+//! is needed. Columns missing from the comment and submission tables are added from the column registry when the
+//! database is opened, so migrations only handle other changes.
+
+use anyhow::{Result, anyhow};
+use log::info;
+use rusqlite::Connection;
+
+use crate::sqlite::{columns::existing_columns, fts::repair_fts};
+
+const SETUP_SCHEMA_VERSION: &str = include_str!("schema_version.sql");
+
+struct Migration {
+    version: i64,
+    description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Check the comment and submission tables are keyed by decoded_reddit_id",
+        up: check_primary_keys,
+    },
+    Migration {
+        version: 2,
+        description: "Replace the full text search triggers and rebuild the index",
+        up: repair_fts,
+    },
+];
+
+/// The schema version of databases created by this version of the importer.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Bring the database up to [`SCHEMA_VERSION`], or fail if it cannot be upgraded.
+pub fn migrate(connection: &Connection) -> Result<()> {
+    let populated: bool = connection.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name IN \
+         ('comment', 'submission', 'subreddit', 'comment_fact', 'submission_fact'))",
+        [],
+        |row| row.get(0),
+    )?;
+    let transaction = connection.unchecked_transaction()?;
+    transaction.execute_batch(SETUP_SCHEMA_VERSION)?;
+    let version: Option<i64> =
+        transaction.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get(0)
+        })?;
+    let version = match version {
+        Some(version) if version > SCHEMA_VERSION => {
+            return Err(anyhow!(
+                "the database has schema version {version}, but this version of the importer only supports up to \
+                 {SCHEMA_VERSION}. Use a newer importer"
+            ));
+        }
+        Some(version) => version,
+        None if populated => 0,
+        None => {
+            // A new database is created with the current layout
+            record(&transaction, SCHEMA_VERSION, "Create the database")?;
+            transaction.commit()?;
+            return Ok(());
+        }
+    };
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > version)
+    {
+        info!(version = migration.version, description = migration.description; "Migrating database");
+        (migration.up)(&transaction)?;
+        record(&transaction, migration.version, migration.description)?;
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+fn record(connection: &Connection, version: i64, description: &str) -> Result<()> {
+    connection.execute(
+        "INSERT INTO schema_version (version, description, applied_utc) VALUES (?, ?, ?)",
+        rusqlite::params![version, description, chrono::Utc::now().timestamp()],
+    )?;
+    Ok(())
+}
+
+/// Early versions of the importer keyed items by an `id` column, which cannot be upgraded in place.
+fn check_primary_keys(connection: &Connection) -> Result<()> {
+    for table in ["comment", "submission"] {
+        let columns = existing_columns(connection, table)?;
+        if !columns.is_empty() && !columns.iter().any(|column| column == "decoded_reddit_id") {
+            return Err(anyhow!(
+                "the {table} table was created by an old version of the importer and has no decoded_reddit_id \
+                 column. It cannot be upgraded, import into a new database instead"
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(connection: &Connection) -> Result<i64> {
+        Ok(
+            connection.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+                row.get(0)
+            })?,
+        )
+    }
+
+    #[test]
+    fn test_new_database() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        migrate(&connection)?;
+        assert_eq!(version(&connection)?, SCHEMA_VERSION);
+        // Opening it again changes nothing
+        migrate(&connection)?;
+        let rows: i64 =
+            connection.query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))?;
+        assert_eq!(rows, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(
+            "CREATE TABLE comment (decoded_reddit_id INTEGER PRIMARY KEY, author TEXT, subreddit TEXT, body TEXT);
+             CREATE VIRTUAL TABLE comment_fts USING fts5(author UNINDEXED, subreddit UNINDEXED, body,
+                                                         content = 'comment', content_rowid = 'rowid');
+             CREATE TRIGGER comment_au AFTER UPDATE ON comment
+                 BEGIN
+                     INSERT INTO comment_fts (rowid, author, subreddit, body)
+                     VALUES (new.rowid, new.author, new.subreddit, new.subreddit);
+                 END;
+             INSERT INTO comment VALUES (1, 'snoo', 'pics', 'hello world');
+             INSERT INTO comment_fts (rowid, author, subreddit, body) VALUES (1, 'snoo', 'pics', 'pics');",
+        )?;
+        migrate(&connection)?;
+        assert_eq!(version(&connection)?, SCHEMA_VERSION);
+
+        let trigger: String = connection.query_row(
+            "SELECT sql FROM sqlite_master WHERE name = 'comment_au'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert!(trigger.contains("new.body"));
+        // The index written by the old trigger is rebuilt from the bodies
+        let matches = |query: &str| -> Result<i64> {
+            Ok(connection.query_row(
+                "SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH ?",
+                [query],
+                |row| row.get(0),
+            )?)
+        };
+        assert_eq!(matches("hello")?, 1);
+        assert_eq!(matches("pics")?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_refuse() -> Result<()> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(
+            "CREATE TABLE comment (id INTEGER PRIMARY KEY, reddit_id TEXT UNIQUE NOT NULL)",
+        )?;
+        assert!(migrate(&connection).is_err());
+
+        let connection = Connection::open_in_memory()?;
+        migrate(&connection)?;
+        record(&connection, SCHEMA_VERSION + 1, "From the future")?;
+        let err = migrate(&connection).unwrap_err();
+        assert!(err.to_string().contains("Use a newer importer"));
+        Ok(())
+    }
+}
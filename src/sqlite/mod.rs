@@ -1,21 +1,54 @@
+mod award;
+mod columns;
+mod conflict;
+mod flair;
+mod fts;
+mod intern;
+mod media;
+mod migrations;
+mod revision;
+
 use std::path::Path;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use log::info;
 use rusqlite::{
-    Connection, OpenFlags, ToSql,
-    types::{ToSqlOutput, Value as OwnedSqliteValue, ValueRef as RefSqliteValue},
+    Connection, OpenFlags, OptionalExtension, ToSql,
+    types::{ToSqlOutput, ValueRef as RefSqliteValue},
 };
 
 use crate::{
-    reddit_types::{comment::Comment, submission::Submission},
+    checksum::VerifiedFile,
+    provenance::Provenance,
+    reddit_types::{RawJson, comment::Comment, submission::Submission, subreddit::Subreddit},
+    sqlite::{
+        award::AwardWriter,
+        columns::existing_columns,
+        conflict::{ChildRows, is_later},
+        flair::{FlairKind, FlairWriter},
+        fts::{FtsIndex, drop_fts_triggers, rebuild, setup_fts},
+        intern::Interner,
+        media::insert_media,
+        migrations::migrate,
+        revision::{COMMENT_TRACKED, RevisionWriter, SUBMISSION_TRACKED},
+    },
     storage::Storage,
 };
 
-const SETUP_COMMENTS: &str = include_str!("comment.sql");
-const COMMENTS_FTS: &str = include_str!("comment_fts.sql");
-const SETUP_SUBMISSIONS: &str = include_str!("submission.sql");
-const SUBMISSIONS_FTS: &str = include_str!("submission_fts.sql");
+pub use columns::{
+    COMMENT_COLUMNS, Column, ColumnProfile, ColumnSelection, SUBMISSION_COLUMNS, value_ref,
+};
+pub use conflict::ConflictPolicy;
+pub use fts::{FtsBuild, FtsIndexOptions, FtsTokenizer, rebuild_fts};
+
+const SETUP_SUBREDDITS: &str = include_str!("subreddit.sql");
+const SETUP_INPUT_FILES: &str = include_str!("input_file.sql");
+const SETUP_AWARDS: &str = include_str!("award.sql");
+const SETUP_MEDIA: &str = include_str!("media.sql");
+const SETUP_FLAIR: &str = include_str!("flair.sql");
+const SETUP_AUTHORS: &str = include_str!("author.sql");
+const SETUP_REVISIONS: &str = include_str!("revision.sql");
+const SETUP_METADATA: &str = include_str!("metadata.sql");
 const PRAGMA: &str = "PRAGMA journal_mode=WAL;
                       PRAGMA recursive_triggers = ON;
                       PRAGMA synchronous = NORMAL;
@@ -33,26 +66,73 @@ const UNSAFE_PRAGMA: &str = "PRAGMA journal_mode=MEMORY;
 const TRANSACTION_SIZE: usize = 10000;
 const BATCH_SIZE: usize = 50;
 
+/// Settings for creating and writing to the database.
+#[derive(Debug, Clone, Default)]
+pub struct SqliteOptions {
+    /// Use pragmas which are faster, but corrupt the database if the import is interrupted
+    pub unsafe_pragma: bool,
+    /// Create full text search tables for comments and submissions
+    pub fts: bool,
+    /// When the full text search tables are written
+    pub fts_build: FtsBuild,
+    /// The tokenizer and indexed columns of the full text search tables, where they differ from the existing ones
+    pub fts_index: FtsIndexOptions,
+    /// Store the raw JSON of each item in a raw_json column
+    pub raw_json: bool,
+    /// Store authors and subreddits once in the author and subreddit tables, and refer to them by id from
+    /// comment_fact and submission_fact. The comment and submission views expose the usual columns.
+    pub normalized: bool,
+    pub comment_columns: ColumnSelection,
+    pub submission_columns: ColumnSelection,
+    /// How to handle items that are already in the database
+    pub on_conflict: ConflictPolicy,
+    /// Record versions of items that differ from the latest version in comment_revision and submission_revision
+    pub revisions: bool,
+}
+
 pub struct Sqlite {
     connection: Connection,
     in_transaction: usize,
     comment_buffer: Vec<Comment>,
     submission_buffer: Vec<Submission>,
+    options: SqliteOptions,
+    /// The index chosen by the options for this database
+    fts_index: FtsIndex,
+    comment_columns: Vec<&'static Column<Comment>>,
+    submission_columns: Vec<&'static Column<Submission>>,
+    interner: Interner,
+    awards: AwardWriter,
+    flair: FlairWriter,
+    comment_revisions: Option<RevisionWriter<Comment>>,
+    submission_revisions: Option<RevisionWriter<Submission>>,
+}
+
+/// The dimension rows an item refers to in the normalised schema.
+#[derive(Clone, Copy)]
+struct DimensionIds {
+    author_id: Option<i64>,
+    decoded_subreddit_id: Option<i64>,
 }
 
 impl Sqlite {
-    pub fn new(filename: &Path, unsafe_pragma: bool, fts: bool) -> Result<Self> {
+    pub fn new(filename: &Path, options: SqliteOptions) -> Result<Self> {
         let connection = Connection::open_with_flags(
             filename,
             OpenFlags::SQLITE_OPEN_NO_MUTEX
                 | OpenFlags::SQLITE_OPEN_READ_WRITE
                 | OpenFlags::SQLITE_OPEN_CREATE,
         )?;
-        Self::with_connection(connection, unsafe_pragma, fts)
+        Self::with_connection(connection, options)
     }
 
-    fn with_connection(connection: Connection, unsafe_pragma: bool, fts: bool) -> Result<Self> {
-        if unsafe_pragma {
+    fn with_connection(connection: Connection, options: SqliteOptions) -> Result<Self> {
+        check_layout(&connection, options.normalized)?;
+        if options.normalized && options.fts {
+            return Err(anyhow!(
+                "full text search is not supported with the normalised schema"
+            ));
+        }
+        if options.unsafe_pragma {
             info!(
                 "Executing in unsafe-mode. Do not interrupt as crashes will corrupt the database."
             );
@@ -60,21 +140,143 @@ impl Sqlite {
         } else {
             connection.execute_batch(PRAGMA)?;
         }
-        connection.execute_batch(SETUP_COMMENTS)?;
-        connection.execute_batch(SETUP_SUBMISSIONS)?;
-        if fts {
-            connection.execute_batch(COMMENTS_FTS)?;
-            connection.execute_batch(SUBMISSIONS_FTS)?;
+        migrate(&connection)?;
+        connection.execute_batch(SETUP_SUBREDDITS)?;
+        if options.normalized {
+            connection.execute_batch(SETUP_AUTHORS)?;
         }
+        // Full text search indexes these columns, and the conflict policy and revisions compare these, so they must be
+        // stored
+        let mut comment_required = options.on_conflict.required_columns().to_vec();
+        let mut submission_required = comment_required.clone();
+        let fts_index = options.fts_index.resolve(&connection)?;
+        if options.fts {
+            fts_index.validate()?;
+            comment_required.extend(fts_index.columns("comment"));
+            submission_required.extend(fts_index.columns("submission"));
+        }
+        if options.revisions {
+            comment_required.extend(COMMENT_TRACKED);
+            submission_required.extend(SUBMISSION_TRACKED);
+        }
+        let comment_columns = setup_content_table(
+            &connection,
+            comment_table(&options),
+            COMMENT_COLUMNS,
+            &options.comment_columns,
+            &comment_required,
+            &options,
+        )?;
+        let submission_columns = setup_content_table(
+            &connection,
+            submission_table(&options),
+            SUBMISSION_COLUMNS,
+            &options.submission_columns,
+            &submission_required,
+            &options,
+        )?;
+        connection.execute_batch(SETUP_INPUT_FILES)?;
+        connection.execute_batch(SETUP_METADATA)?;
+        connection.execute_batch(SETUP_AWARDS)?;
+        connection.execute_batch(SETUP_MEDIA)?;
+        connection.execute_batch(SETUP_FLAIR)?;
+        if options.revisions {
+            connection.execute_batch(SETUP_REVISIONS)?;
+        }
+        if options.normalized {
+            create_compatibility_view(&connection, "comment", "comment_fact")?;
+            create_compatibility_view(&connection, "submission", "submission_fact")?;
+        }
+        if options.fts {
+            match options.fts_build {
+                FtsBuild::Triggers => setup_fts(&connection, &fts_index)?,
+                FtsBuild::PostLoad => drop_fts_triggers(&connection)?,
+            }
+        }
+        // With the normalised schema, stored rows are read through the views, which have the author names
+        let comment_revisions = options.revisions.then(|| {
+            RevisionWriter::new(
+                COMMENT_COLUMNS,
+                COMMENT_TRACKED,
+                "comment",
+                "comment_revision",
+            )
+        });
+        let submission_revisions = options.revisions.then(|| {
+            RevisionWriter::new(
+                SUBMISSION_COLUMNS,
+                SUBMISSION_TRACKED,
+                "submission",
+                "submission_revision",
+            )
+        });
         connection.execute_batch("BEGIN DEFERRED")?;
         Ok(Sqlite {
             connection,
             in_transaction: 0,
             comment_buffer: Vec::with_capacity(BATCH_SIZE),
             submission_buffer: Vec::with_capacity(BATCH_SIZE),
+            options,
+            fts_index,
+            comment_columns,
+            submission_columns,
+            interner: Interner::default(),
+            awards: AwardWriter::default(),
+            flair: FlairWriter::default(),
+            comment_revisions,
+            submission_revisions,
         })
     }
 
+    /// Record the checksum of an input file, replacing any earlier record for the same path.
+    pub fn record_input_file(&mut self, file: &VerifiedFile) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO input_file (path, size, sha256, expected_sha256, verified, verified_utc) \
+             VALUES (?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (path) DO UPDATE SET size = excluded.size, sha256 = excluded.sha256, \
+             expected_sha256 = excluded.expected_sha256, verified = excluded.verified, \
+             verified_utc = excluded.verified_utc",
+            rusqlite::params![
+                file.path.to_string_lossy(),
+                file.size,
+                file.sha256,
+                file.expected,
+                file.is_match(),
+                chrono::Utc::now().timestamp(),
+            ],
+        )?;
+        // Keep the record of a verified file even if the import that follows fails
+        self.commit()?;
+        Ok(())
+    }
+
+    /// Record how the database was produced, under a new import_id, returning the id.
+    pub fn record_provenance(&mut self, provenance: &Provenance) -> Result<i64> {
+        let import_id: i64 = self.connection.query_row(
+            "SELECT COALESCE(MAX(import_id), 0) + 1 FROM metadata",
+            [],
+            |row| row.get(0),
+        )?;
+        let serde_json::Value::Object(fields) = serde_json::to_value(provenance)? else {
+            return Err(anyhow!("provenance must serialize to an object"));
+        };
+        let mut statement = self
+            .connection
+            .prepare_cached("INSERT INTO metadata (import_id, key, value) VALUES (?, ?, ?)")?;
+        for (key, value) in fields {
+            let value = match value {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(value) => Some(value),
+                value => Some(value.to_string()),
+            };
+            statement.execute(rusqlite::params![import_id, key, value])?;
+        }
+        drop(statement);
+        // Provenance is recorded after finish, so nothing else would commit it
+        self.commit()?;
+        Ok(import_id)
+    }
+
     fn check_transaction(&mut self) -> Result<()> {
         if self.in_transaction >= TRANSACTION_SIZE {
             self.commit()?;
@@ -91,91 +293,120 @@ impl Sqlite {
         Ok(())
     }
 
+    /// How to write the award counts, media and flair of an incoming item under the conflict policy. `earlier` finds
+    /// the retrieved_on of a version of the item earlier in the same batch, which is not in `table` yet.
+    fn child_rows(
+        &self,
+        table: &str,
+        decoded_reddit_id: i64,
+        retrieved_on: Option<i64>,
+        earlier: impl FnOnce() -> Option<Option<i64>>,
+    ) -> Result<ChildRows> {
+        let policy = self.options.on_conflict;
+        if policy != ConflictPolicy::KeepLatest {
+            return Ok(policy.child_rows(retrieved_on, None));
+        }
+        let mut stored: Option<Option<i64>> = self
+            .connection
+            .prepare_cached(&format!(
+                "SELECT retrieved_on FROM {table} WHERE decoded_reddit_id = ?"
+            ))?
+            .query_row([decoded_reddit_id], |row| row.get(0))
+            .optional()?;
+        if let Some(earlier) = earlier() {
+            // The batch keeps whichever of the two versions is later
+            stored = Some(match stored {
+                Some(stored) if !is_later(earlier, stored) => stored,
+                _ => earlier,
+            });
+        }
+        Ok(policy.child_rows(retrieved_on, stored))
+    }
+
     fn flush_comments(&mut self) -> Result<()> {
         if self.comment_buffer.is_empty() {
             return Ok(());
         }
 
         let batch_size = self.comment_buffer.len();
-        let values_clause = vec![
-            "(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
-            batch_size
-        ]
-        .join(", ");
+        let columns = insert_columns(&self.comment_columns, &self.options);
+        let values_clause = vec![placeholders(columns.len()); batch_size].join(", ");
+        let table = comment_table(&self.options);
         let sql = format!(
-            "INSERT INTO comment \
-            (decoded_reddit_id, reddit_id, permalink, author, author_premium, \
-             subreddit, decoded_subreddit_id, subreddit_type, \
-             body, score, ups, \
-             downs, created_utc, edited_utc, retrieved_on, \
-             parent_type, parent_id, decoded_parent_id, distinguished, \
-             stickied, parent_is_post, is_submitter, archived, locked, collapsed) \
-            VALUES {values_clause} \
-            ON CONFLICT DO NOTHING"
+            "INSERT INTO {table} ({}) VALUES {values_clause} {}",
+            columns.join(", "),
+            self.options.on_conflict.clause(table, &columns)
         );
 
         let mut stmt = self.connection.prepare_cached(&sql)?;
-        let mut params = Vec::with_capacity(batch_size);
-        for comment in &self.comment_buffer {
+        let mut params = Vec::with_capacity(batch_size * columns.len());
+        let has_author = has_column(&self.comment_columns, "author");
+        let has_subreddit = has_column(&self.comment_columns, "subreddit");
+        for (index, comment) in self.comment_buffer.iter().enumerate() {
             let decoded_reddit_id = i64::from_str_radix(&comment.id, 36)?;
-            let decoded_parent_id = comment
-                .parent_id
-                .as_ref()
-                .and_then(|parent_id| i64::from_str_radix(&parent_id.parent_id, 26).ok());
-
-            let row_params = [
-                ToSqlOutput::Owned(OwnedSqliteValue::from(decoded_reddit_id)),
-                comment.id.to_sql()?,
-                comment.permalink.to_sql()?,
-                comment.author.to_sql()?,
-                comment.author_premium.to_sql()?,
-                comment.subreddit.to_sql()?,
-                comment.subreddit_id.decoded_parent_id.to_sql()?,
-                ToSqlOutput::Borrowed(comment.subreddit_type.map_or(RefSqliteValue::Null, |ty| {
-                    RefSqliteValue::Text(<&'static str>::from(ty).as_bytes())
-                })),
-                comment.body.to_sql()?,
-                comment.score.to_sql()?,
-                comment.ups.to_sql()?,
-                comment.downs.to_sql()?,
-                comment.created_utc.to_sql()?,
-                comment.edited.to_sql()?,
-                comment.retrieved_on.to_sql()?,
-                ToSqlOutput::Owned(
-                    comment
-                        .parent_id
-                        .as_ref()
-                        .and_then(|parent_id| parent_id.parent_type)
-                        .map_or(OwnedSqliteValue::Null, |ty| {
-                            OwnedSqliteValue::Integer(ty.into())
-                        }),
-                ),
-                ToSqlOutput::Borrowed(
-                    comment
-                        .parent_id
-                        .as_ref()
-                        .map_or(RefSqliteValue::Null, |parent_id| {
-                            RefSqliteValue::Text(parent_id.parent_id.as_bytes())
-                        }),
-                ),
-                ToSqlOutput::Owned(OwnedSqliteValue::from(decoded_parent_id)),
-                ToSqlOutput::Borrowed(comment.distinguished.map_or(
-                    RefSqliteValue::Null,
-                    |distinguished| {
-                        RefSqliteValue::Text(<&'static str>::from(distinguished).as_bytes())
+            if let Some(revisions) = &self.comment_revisions {
+                let earlier = self.comment_buffer[..index]
+                    .iter()
+                    .rfind(|earlier| earlier.id == comment.id);
+                revisions.record(
+                    &self.connection,
+                    decoded_reddit_id,
+                    comment,
+                    earlier,
+                    comment.retrieved_on,
+                    comment.source_file.as_deref(),
+                )?;
+            }
+            let ids = if self.options.normalized {
+                let decoded_subreddit_id = Some(comment.subreddit_id.decoded_parent_id);
+                Some(DimensionIds {
+                    author_id: if has_author {
+                        Some(self.interner.author_id(&self.connection, &comment.author)?)
+                    } else {
+                        None
+                    },
+                    decoded_subreddit_id: if has_subreddit {
+                        self.interner.subreddit_id(
+                            &self.connection,
+                            Some(&comment.subreddit),
+                            decoded_subreddit_id,
+                        )?
+                    } else {
+                        decoded_subreddit_id
                     },
-                )),
-                comment.stickied.to_sql()?,
-                comment.parent_is_post.to_sql()?,
-                comment.is_submitter.to_sql()?,
-                comment.archived.to_sql()?,
-                comment.locked.to_sql()?,
-                comment.collapsed.to_sql()?,
-            ];
-            params.push(row_params);
+                })
+            } else {
+                None
+            };
+            extend_row(&mut params, &self.comment_columns, comment, ids)?;
+            if self.options.raw_json {
+                params.push(comment.raw_json.to_sql()?);
+            }
+            let children =
+                self.child_rows(table, decoded_reddit_id, comment.retrieved_on, || {
+                    self.comment_buffer[..index]
+                        .iter()
+                        .rfind(|earlier| earlier.id == comment.id)
+                        .map(|earlier| earlier.retrieved_on)
+                })?;
+            self.awards.insert(
+                &self.connection,
+                "comment_award",
+                decoded_reddit_id,
+                &comment.all_awardings,
+                comment.gildings.as_ref(),
+                children,
+            )?;
+            self.flair.insert(
+                &self.connection,
+                FlairKind::Author,
+                Some(comment.subreddit_id.decoded_parent_id),
+                comment.author_flair(),
+                children,
+            )?;
         }
 
-        stmt.execute(rusqlite::params_from_iter(params.into_iter().flatten()))?;
+        stmt.execute(rusqlite::params_from_iter(params))?;
 
         self.in_transaction += batch_size;
         self.comment_buffer.clear();
@@ -188,101 +419,100 @@ impl Sqlite {
         }
 
         let batch_size = self.submission_buffer.len();
-        let values_clause = vec!["(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"; batch_size].join(", ");
+        let columns = insert_columns(&self.submission_columns, &self.options);
+        let values_clause = vec![placeholders(columns.len()); batch_size].join(", ");
+        let table = submission_table(&self.options);
         let sql = format!(
-            "INSERT INTO submission \
-            (decoded_reddit_id, reddit_id, author, author_premium, title, subreddit, decoded_subreddit_id, subreddit_subscribers, selftext, permalink, \
-             domain, url, score, ups, downs, created_utc, edited_utc, retrieved_on, is_self, over_18, \
-             spoiler, pinned, stickied, num_comments, num_crossposts, author_flair_text, author_flair_template_id, link_flair_text, link_flair_template_id, \
-             is_created_from_ads_ui, is_gallery, is_video, is_original_content, is_reddit_media_domain, contest_mode, quarantine, \
-             subreddit_type, suggested_sort, decoded_crosspost_parent_id, post_hint, removed_by_category) \
-            VALUES {values_clause} \
-            ON CONFLICT DO NOTHING"
+            "INSERT INTO {table} ({}) VALUES {values_clause} {}",
+            columns.join(", "),
+            self.options.on_conflict.clause(table, &columns)
         );
 
         let mut stmt = self.connection.prepare_cached(&sql)?;
-        let mut params = Vec::with_capacity(batch_size);
-
-        for submission in &self.submission_buffer {
+        let mut params = Vec::with_capacity(batch_size * columns.len());
+        let has_author = has_column(&self.submission_columns, "author");
+        let has_subreddit = has_column(&self.submission_columns, "subreddit");
+        for (index, submission) in self.submission_buffer.iter().enumerate() {
             let decoded_reddit_id = i64::from_str_radix(&submission.id, 36)?;
-
-            let row_params = [
-                ToSqlOutput::Owned(OwnedSqliteValue::from(decoded_reddit_id)),
-                submission.id.to_sql()?,
-                submission.author.to_sql()?,
-                submission.author_premium.to_sql()?,
-                submission.title.to_sql()?,
-                submission.subreddit.to_sql()?,
-                ToSqlOutput::Owned(OwnedSqliteValue::from(
-                    submission
-                        .subreddit_id
-                        .as_ref()
-                        .map(|s| s.decoded_parent_id),
-                )),
-                submission.subreddit_subscribers.to_sql()?,
-                submission.selftext.to_sql()?,
-                submission.permalink.to_sql()?,
-                submission.domain.to_sql()?,
-                submission.url.to_sql()?,
-                submission.score.to_sql()?,
-                submission.ups.to_sql()?,
-                submission.downs.to_sql()?,
-                submission.created_utc.to_sql()?,
-                submission.edited_utc.to_sql()?,
-                submission.retrieved_on.to_sql()?,
-                submission.is_self.to_sql()?,
-                submission.over_18.to_sql()?,
-                submission.spoiler.to_sql()?,
-                submission.pinned.to_sql()?,
-                submission.stickied.to_sql()?,
-                submission.num_comments.to_sql()?,
-                submission.num_crossposts.to_sql()?,
-                submission.author_flair_text.to_sql()?,
-                submission.author_flair_template_id.to_sql()?,
-                submission.link_flair_text.to_sql()?,
-                submission.link_flair_template_id.to_sql()?,
-                submission.is_created_from_ads_ui.to_sql()?,
-                submission.is_gallery.to_sql()?,
-                submission.is_video.to_sql()?,
-                submission.is_original_content.to_sql()?,
-                submission.is_reddit_media_domain.to_sql()?,
-                submission.contest_mode.to_sql()?,
-                submission.quarantine.to_sql()?,
-                ToSqlOutput::Borrowed(
-                    submission
-                        .subreddit_type
-                        .map_or(RefSqliteValue::Null, |ty| {
-                            RefSqliteValue::Text(<&'static str>::from(ty).as_bytes())
-                        }),
-                ),
-                ToSqlOutput::Borrowed(
-                    submission
-                        .suggested_sort
-                        .map_or(RefSqliteValue::Null, |sort| {
-                            RefSqliteValue::Text(<&'static str>::from(sort).as_bytes())
-                        }),
-                ),
-                ToSqlOutput::Owned(OwnedSqliteValue::from(
-                    submission
-                        .crosspost_parent
-                        .as_ref()
-                        .map(|id| id.decoded_parent_id),
-                )),
-                ToSqlOutput::Borrowed(submission.post_hint.map_or(RefSqliteValue::Null, |hint| {
-                    RefSqliteValue::Text(<&'static str>::from(hint).as_bytes())
-                })),
-                ToSqlOutput::Borrowed(
-                    submission
-                        .removed_by_category
-                        .map_or(RefSqliteValue::Null, |cat| {
-                            RefSqliteValue::Text(<&'static str>::from(cat).as_bytes())
-                        }),
-                ),
-            ];
-            params.push(row_params);
+            if let Some(revisions) = &self.submission_revisions {
+                let earlier = self.submission_buffer[..index]
+                    .iter()
+                    .rfind(|earlier| earlier.id == submission.id);
+                revisions.record(
+                    &self.connection,
+                    decoded_reddit_id,
+                    submission,
+                    earlier,
+                    submission.retrieved_on,
+                    submission.source_file.as_deref(),
+                )?;
+            }
+            let ids = if self.options.normalized {
+                let decoded_subreddit_id = submission
+                    .subreddit_id
+                    .as_ref()
+                    .map(|id| id.decoded_parent_id);
+                Some(DimensionIds {
+                    author_id: match submission.author.as_deref() {
+                        Some(author) if has_author => {
+                            Some(self.interner.author_id(&self.connection, author)?)
+                        }
+                        _ => None,
+                    },
+                    decoded_subreddit_id: if has_subreddit {
+                        self.interner.subreddit_id(
+                            &self.connection,
+                            submission.subreddit.as_deref(),
+                            decoded_subreddit_id,
+                        )?
+                    } else {
+                        decoded_subreddit_id
+                    },
+                })
+            } else {
+                None
+            };
+            extend_row(&mut params, &self.submission_columns, submission, ids)?;
+            if self.options.raw_json {
+                params.push(submission.raw_json.to_sql()?);
+            }
+            let children =
+                self.child_rows(table, decoded_reddit_id, submission.retrieved_on, || {
+                    self.submission_buffer[..index]
+                        .iter()
+                        .rfind(|earlier| earlier.id == submission.id)
+                        .map(|earlier| earlier.retrieved_on)
+                })?;
+            self.awards.insert(
+                &self.connection,
+                "submission_award",
+                decoded_reddit_id,
+                &submission.all_awardings,
+                submission.gildings.as_ref(),
+                children,
+            )?;
+            insert_media(&self.connection, decoded_reddit_id, submission, children)?;
+            let decoded_subreddit_id = submission
+                .subreddit_id
+                .as_ref()
+                .map(|id| id.decoded_parent_id);
+            self.flair.insert(
+                &self.connection,
+                FlairKind::Author,
+                decoded_subreddit_id,
+                submission.author_flair(),
+                children,
+            )?;
+            self.flair.insert(
+                &self.connection,
+                FlairKind::Link,
+                decoded_subreddit_id,
+                submission.link_flair(),
+                children,
+            )?;
         }
 
-        stmt.execute(rusqlite::params_from_iter(params.into_iter().flatten()))?;
+        stmt.execute(rusqlite::params_from_iter(params))?;
 
         self.in_transaction += batch_size;
         self.submission_buffer.clear();
@@ -290,6 +520,222 @@ impl Sqlite {
     }
 }
 
+/// Refuse to open a database created with the other schema, as the tables and views would be mixed up.
+fn check_layout(connection: &Connection, normalized: bool) -> Result<()> {
+    let existing: Option<String> = connection
+        .query_row(
+            "SELECT type FROM sqlite_master WHERE name = 'comment'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match existing.as_deref() {
+        Some("table") if normalized => Err(anyhow!(
+            "the database was created without the normalised schema"
+        )),
+        Some("view") if !normalized => Err(anyhow!(
+            "the database was created with the normalised schema, pass --normalize"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Recreate `view` over the normalised `fact_table`, joining the author and subreddit names back in.
+///
+/// The view is generated from the columns the table has, so it includes columns added by later imports.
+fn create_compatibility_view(connection: &Connection, view: &str, fact_table: &str) -> Result<()> {
+    let columns: Vec<String> = connection
+        .prepare("SELECT name FROM pragma_table_info(?)")?
+        .query_map([fact_table], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let select: Vec<String> = columns
+        .iter()
+        .map(|column| match column.as_str() {
+            "author_id" => "author.name AS author".to_string(),
+            // Subreddits of content without a subreddit id have negative ids, which are not exposed
+            "decoded_subreddit_id" => "subreddit.display_name AS subreddit, \
+                CASE WHEN f.decoded_subreddit_id >= 0 THEN f.decoded_subreddit_id END AS decoded_subreddit_id"
+                .to_string(),
+            column => format!("f.{column}"),
+        })
+        .collect();
+    connection.execute_batch(&format!(
+        "DROP VIEW IF EXISTS {view}; \
+         CREATE VIEW {view} AS SELECT {} FROM {fact_table} AS f \
+         LEFT JOIN author USING (author_id) \
+         LEFT JOIN subreddit USING (decoded_subreddit_id);",
+        select.join(", ")
+    ))?;
+    Ok(())
+}
+
+fn comment_table(options: &SqliteOptions) -> &'static str {
+    if options.normalized {
+        "comment_fact"
+    } else {
+        "comment"
+    }
+}
+
+fn submission_table(options: &SqliteOptions) -> &'static str {
+    if options.normalized {
+        "submission_fact"
+    } else {
+        "submission"
+    }
+}
+
+/// The name `column` is stored under.
+///
+/// The normalised schema replaces the author with author_id, and keeps only the subreddit id.
+fn stored_name(column: &'static str, normalized: bool) -> Option<&'static str> {
+    match column {
+        "author" if normalized => Some("author_id"),
+        "subreddit" if normalized => None,
+        column => Some(column),
+    }
+}
+
+fn has_column<T>(columns: &[&Column<T>], name: &str) -> bool {
+    columns.iter().any(|column| column.name == name)
+}
+
+/// Create `table` with the columns of `registry` chosen by `selection`, or add any it lacks if it exists.
+///
+/// Returns the columns to write, which also include the `required` columns and those the table already has.
+fn setup_content_table<T>(
+    connection: &Connection,
+    table: &str,
+    registry: &'static [Column<T>],
+    selection: &ColumnSelection,
+    required: &[&str],
+    options: &SqliteOptions,
+) -> Result<Vec<&'static Column<T>>> {
+    let existing = existing_columns(connection, table)?;
+    let mut existing_fields = existing.clone();
+    if options.normalized {
+        for name in &existing {
+            match name.as_str() {
+                "author_id" => existing_fields.push("author".to_string()),
+                "decoded_subreddit_id" => existing_fields.push("subreddit".to_string()),
+                _ => {}
+            }
+        }
+    }
+    let mut columns = selection.resolve(registry, required, &existing_fields)?;
+    // The normalised schema finds the subreddit name through the subreddit id
+    if options.normalized
+        && has_column(&columns, "subreddit")
+        && !has_column(&columns, "decoded_subreddit_id")
+    {
+        let mut required = required.to_vec();
+        required.push("decoded_subreddit_id");
+        columns = selection.resolve(registry, &required, &existing_fields)?;
+    }
+
+    let mut definitions: Vec<(&str, String)> = columns
+        .iter()
+        .filter_map(|column| {
+            let name = stored_name(column.name, options.normalized)?;
+            let definition = match name {
+                "author_id" => "INTEGER REFERENCES author (author_id)".to_string(),
+                "decoded_subreddit_id" if options.normalized => format!(
+                    "{} REFERENCES subreddit (decoded_subreddit_id)",
+                    column.definition
+                ),
+                _ => column.definition.to_string(),
+            };
+            Some((name, definition))
+        })
+        .collect();
+    if options.raw_json {
+        definitions.push(("raw_json", "BLOB".to_string()));
+    }
+
+    if existing.is_empty() {
+        let definitions: Vec<_> = definitions
+            .iter()
+            .map(|(name, definition)| format!("{name} {definition}"))
+            .collect();
+        connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} ({});",
+            definitions.join(", ")
+        ))?;
+    } else {
+        for (name, definition) in &definitions {
+            // Constraints such as NOT NULL cannot be added to existing rows
+            let sql_type = definition.split(' ').next().unwrap_or_default();
+            ensure_column(connection, table, name, sql_type)?;
+        }
+    }
+    Ok(columns)
+}
+
+/// The stored names of `columns` in bind order, followed by raw_json if it is kept.
+fn insert_columns<T>(columns: &[&Column<T>], options: &SqliteOptions) -> Vec<&'static str> {
+    let mut names: Vec<_> = columns
+        .iter()
+        .filter_map(|column| stored_name(column.name, options.normalized))
+        .collect();
+    if options.raw_json {
+        names.push("raw_json");
+    }
+    names
+}
+
+/// Append the values of `item` for `columns` to `params`, swapping names for `ids` with the normalised schema.
+fn extend_row<'a, T>(
+    params: &mut Vec<ToSqlOutput<'a>>,
+    columns: &[&Column<T>],
+    item: &'a T,
+    ids: Option<DimensionIds>,
+) -> rusqlite::Result<()> {
+    for column in columns {
+        match (column.name, ids) {
+            ("author", Some(ids)) => params.push(ToSqlOutput::Owned(ids.author_id.into())),
+            ("subreddit", Some(_)) => {}
+            ("decoded_subreddit_id", Some(ids)) => {
+                params.push(ToSqlOutput::Owned(ids.decoded_subreddit_id.into()))
+            }
+            _ => params.push((column.value)(item)?),
+        }
+    }
+    Ok(())
+}
+
+/// A parenthesized list of `columns` bind parameters, for one row of a `VALUES` clause.
+fn placeholders(columns: usize) -> String {
+    format!("({})", vec!["?"; columns].join(", "))
+}
+
+fn ensure_column(
+    connection: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let exists: bool = connection.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        connection.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+impl ToSql for RawJson {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Borrowed(match self {
+            RawJson::Plain(text) => RefSqliteValue::Text(text.as_bytes()),
+            RawJson::Zstd(blob) => RefSqliteValue::Blob(blob),
+        }))
+    }
+}
+
 impl Storage for Sqlite {
     fn insert_comment(&mut self, comment: Comment) -> Result<usize> {
         // Validate the base36 ID early to maintain expected error behavior
@@ -318,6 +764,56 @@ impl Storage for Sqlite {
         self.check_transaction()?;
         Ok(0)
     }
+
+    fn insert_subreddit(&mut self, subreddit: Subreddit) -> Result<usize> {
+        let reddit_id = subreddit.id.trim_start_matches("t5_");
+        let decoded_subreddit_id = i64::from_str_radix(reddit_id, 36)?;
+        // Fill in rows created by the normalised schema, but keep the first metadata seen for a subreddit
+        let mut stmt = self.connection.prepare_cached(
+            "INSERT INTO subreddit \
+            (decoded_subreddit_id, reddit_id, display_name, title, public_description, description, \
+             subscribers, created_utc, over_18, subreddit_type, lang, url, retrieved_on, raw_json) \
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+            ON CONFLICT (decoded_subreddit_id) DO UPDATE SET reddit_id = excluded.reddit_id, \
+             display_name = excluded.display_name, title = excluded.title, \
+             public_description = excluded.public_description, description = excluded.description, \
+             subscribers = excluded.subscribers, created_utc = excluded.created_utc, over_18 = excluded.over_18, \
+             subreddit_type = excluded.subreddit_type, lang = excluded.lang, url = excluded.url, \
+             retrieved_on = excluded.retrieved_on, raw_json = excluded.raw_json \
+            WHERE subreddit.reddit_id IS NULL",
+        )?;
+        stmt.execute(rusqlite::params![
+            decoded_subreddit_id,
+            reddit_id,
+            subreddit.display_name,
+            subreddit.title,
+            subreddit.public_description,
+            subreddit.description,
+            subreddit.subscribers,
+            subreddit.created_utc,
+            subreddit.over18,
+            subreddit.subreddit_type.as_ref().map(|ty| ty.as_str()),
+            subreddit.lang,
+            subreddit.url,
+            subreddit.retrieved_on,
+            subreddit.raw_json,
+        ])?;
+        drop(stmt);
+
+        self.in_transaction += 1;
+        self.check_transaction()?;
+        Ok(0)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.commit()?;
+        if self.options.fts && self.options.fts_build == FtsBuild::PostLoad {
+            info!("Building full text search indexes");
+            rebuild(&self.connection, &self.fts_index)?;
+            self.commit()?;
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Sqlite {
@@ -338,15 +834,26 @@ impl Drop for Sqlite {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::checksum::VerificationStatus;
+    use crate::filter::Filter;
+    use crate::provenance::InputFile;
+    use crate::reddit_types::RawJsonFormat;
     use crate::reddit_types::comment::Comment;
     use crate::reddit_types::submission::Submission;
+    use crate::reddit_types::subreddit::Subreddit;
     use rusqlite::params;
 
     impl Sqlite {
         #[cfg(test)]
         fn new_test(fts: bool) -> Result<Self> {
             let connection = Connection::open_in_memory()?;
-            Self::with_connection(connection, false, fts)
+            Self::with_connection(
+                connection,
+                SqliteOptions {
+                    fts,
+                    ..Default::default()
+                },
+            )
         }
     }
 
@@ -435,6 +942,581 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_insert_subreddits() -> Result<()> {
+        let mut storage = Sqlite::new_test(false)?;
+        let subreddits = include_str!("../../test_data/test_subreddits.json");
+        let comments = include_str!("../../test_data/test_comments.json");
+
+        for line in subreddits.lines() {
+            let subreddit: Subreddit = serde_json::from_str(line)?;
+            storage.insert_subreddit(subreddit)?;
+        }
+        for line in comments.lines() {
+            let comment: Comment = serde_json::from_str(line)?;
+            storage.insert_comment(comment)?;
+        }
+        storage.commit()?;
+
+        let count: i64 =
+            storage
+                .connection
+                .query_row("SELECT COUNT(*) FROM subreddit", [], |row| row.get(0))?;
+        assert_eq!(count as usize, subreddits.lines().count());
+
+        let (display_name, subscribers, over_18): (String, Option<i64>, bool) =
+            storage.connection.query_row(
+                "SELECT display_name, subscribers, over_18 FROM subreddit WHERE reddit_id = 'ar9yc0'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+        assert_eq!(display_name, "SammysMakeup");
+        assert_eq!(subscribers, Some(1234));
+        assert!(over_18);
+
+        // Content joins to subreddit metadata through decoded_subreddit_id
+        let joined: i64 = storage.connection.query_row(
+            "SELECT COUNT(*) FROM comment JOIN subreddit USING (decoded_subreddit_id) \
+             WHERE comment.subreddit = subreddit.display_name",
+            [],
+            |row| row.get(0),
+        )?;
+        assert!(joined > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalized() -> Result<()> {
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                normalized: true,
+                ..Default::default()
+            },
+        )?;
+        let comments = include_str!("../../test_data/test_comments.json");
+        let submissions = include_str!("../../test_data/test_submissions.json");
+        let subreddits = include_str!("../../test_data/test_subreddits.json");
+
+        let first_comment: Comment = serde_json::from_str(comments.lines().next().unwrap())?;
+        for line in comments.lines() {
+            let comment: Comment = serde_json::from_str(line)?;
+            storage.insert_comment(comment)?;
+        }
+        for line in submissions.lines() {
+            let mut submission: Submission = serde_json::from_str(line)?;
+            submission.subreddit = Some(first_comment.subreddit.clone());
+            submission.subreddit_id = None;
+            storage.insert_submission(submission)?;
+        }
+        // Metadata imported after content fills in the interned subreddit rows
+        for line in subreddits.lines() {
+            let subreddit: Subreddit = serde_json::from_str(line)?;
+            storage.insert_subreddit(subreddit)?;
+        }
+        storage.commit()?;
+
+        let count: i64 =
+            storage
+                .connection
+                .query_row("SELECT COUNT(*) FROM comment", [], |row| row.get(0))?;
+        assert_eq!(count as usize, comments.lines().count());
+
+        let (author, subreddit): (String, String) = storage.connection.query_row(
+            "SELECT author, subreddit FROM comment WHERE reddit_id = ?",
+            params![first_comment.id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(author, first_comment.author);
+        assert_eq!(subreddit, first_comment.subreddit);
+
+        let (authors, distinct_authors): (i64, i64) = storage.connection.query_row(
+            "SELECT (SELECT COUNT(*) FROM author), \
+             (SELECT COUNT(DISTINCT author) FROM \
+              (SELECT author FROM comment UNION ALL SELECT author FROM submission))",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(authors, distinct_authors);
+
+        // Submissions without a subreddit id share the subreddit row of comments with the same name
+        let (subreddit, decoded_subreddit_id): (String, Option<i64>) =
+            storage.connection.query_row(
+                "SELECT DISTINCT subreddit, decoded_subreddit_id FROM submission",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+        assert_eq!(subreddit, first_comment.subreddit);
+        assert_eq!(
+            decoded_subreddit_id,
+            Some(first_comment.subreddit_id.decoded_parent_id)
+        );
+
+        let (metadata_rows, stubs): (i64, i64) = storage.connection.query_row(
+            "SELECT COUNT(reddit_id), COUNT(*) - COUNT(reddit_id) FROM subreddit",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(metadata_rows as usize, subreddits.lines().count());
+        assert!(stubs > 0);
+
+        assert!(check_layout(&storage.connection, false).is_err());
+        assert!(check_layout(&storage.connection, true).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalized_unknown_subreddit_id() -> Result<()> {
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                normalized: true,
+                ..Default::default()
+            },
+        )?;
+        let submissions = include_str!("../../test_data/test_submissions.json");
+        for (index, line) in submissions.lines().take(2).enumerate() {
+            let mut submission: Submission = serde_json::from_str(line)?;
+            submission.subreddit = Some(format!("unknown{index}"));
+            submission.subreddit_id = None;
+            storage.insert_submission(submission)?;
+        }
+        storage.commit()?;
+
+        let ids: Vec<i64> = storage
+            .connection
+            .prepare("SELECT decoded_subreddit_id FROM submission_fact ORDER BY 1")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        assert_eq!(ids, [-2, -1]);
+        let visible: Option<i64> = storage.connection.query_row(
+            "SELECT MAX(decoded_subreddit_id) FROM submission",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(visible, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_awards() -> Result<()> {
+        let mut storage = Sqlite::new_test(false)?;
+        let comments = include_str!("../../test_data/test_comments.json");
+        let submissions = include_str!("../../test_data/test_submissions.json");
+
+        let mut comment_json: serde_json::Value =
+            serde_json::from_str(comments.lines().next().unwrap())?;
+        comment_json["all_awardings"] = serde_json::json!([
+            {"id": "gid_2", "name": "Gold", "coin_price": 500, "count": 1},
+            {"id": "award_5f123e3d-4f48-42f4-9c11-e98b566d5897", "name": "Wholesome", "count": 3}
+        ]);
+        comment_json["total_awards_received"] = 4.into();
+        let comment: Comment = serde_json::from_value(comment_json)?;
+        let decoded_id = i64::from_str_radix(&comment.id, 36)?;
+        storage.insert_comment(comment)?;
+        for line in submissions.lines() {
+            let submission: Submission = serde_json::from_str(line)?;
+            storage.insert_submission(submission)?;
+        }
+        storage.commit()?;
+
+        let awards: i64 =
+            storage
+                .connection
+                .query_row("SELECT COUNT(*) FROM award", [], |row| row.get(0))?;
+        assert_eq!(awards, 2);
+
+        let (total_awards_received, counted): (Option<i64>, i64) = storage.connection.query_row(
+            "SELECT total_awards_received, SUM(count) FROM comment \
+             JOIN comment_award USING (decoded_reddit_id) WHERE decoded_reddit_id = ?",
+            params![decoded_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(total_awards_received, Some(4));
+        assert_eq!(counted, 4);
+
+        // Old submissions only carry a gilded count
+        let gilded: Option<i64> = storage.connection.query_row(
+            "SELECT gilded FROM submission WHERE reddit_id = '87'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(gilded, Some(6));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_submission_media() -> Result<()> {
+        let mut storage = Sqlite::new_test(false)?;
+        let submissions = include_str!("../../test_data/test_submissions.json");
+        for line in submissions.lines() {
+            let submission: Submission = serde_json::from_str(line)?;
+            storage.insert_submission(submission)?;
+        }
+        storage.commit()?;
+
+        // jk3lbh embeds images in its text, but is not a gallery
+        let (count, positions, mime_type, width): (i64, i64, String, i64) =
+            storage.connection.query_row(
+                "SELECT COUNT(*), COUNT(position), MAX(mime_type), MAX(width) \
+                 FROM submission_media JOIN submission USING (decoded_reddit_id) \
+                 WHERE reddit_id = 'jk3lbh'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+        assert!(count > 1);
+        assert_eq!(positions, 0);
+        assert_eq!(mime_type, "image/jpg");
+        assert!(width > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flair() -> Result<()> {
+        let mut storage = Sqlite::new_test(false)?;
+        let comments = include_str!("../../test_data/test_comments.json");
+        let submissions = include_str!("../../test_data/test_submissions.json");
+        for line in comments.lines() {
+            let comment: Comment = serde_json::from_str(line)?;
+            storage.insert_comment(comment)?;
+        }
+        for line in submissions.lines() {
+            let submission: Submission = serde_json::from_str(line)?;
+            storage.insert_submission(submission)?;
+        }
+        storage.commit()?;
+
+        let (author_flair, template_ids): (i64, i64) = storage.connection.query_row(
+            "SELECT (SELECT COUNT(*) FROM author_flair), \
+             (SELECT COUNT(DISTINCT template_id) FROM \
+              (SELECT author_flair_template_id AS template_id FROM comment \
+               UNION ALL SELECT author_flair_template_id FROM submission))",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert!(author_flair > 0);
+        assert_eq!(author_flair, template_ids);
+
+        let (text, richtext): (String, String) = storage.connection.query_row(
+            "SELECT link_flair.text, link_flair.richtext FROM submission \
+             JOIN link_flair ON link_flair.template_id = submission.link_flair_template_id \
+             AND link_flair.decoded_subreddit_id = submission.decoded_subreddit_id \
+             WHERE link_flair.flair_type = 'richtext' ORDER BY 1 LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(text, "Game Thread");
+        assert_eq!(richtext, r#"[{"e":"text","t":"Game Thread"}]"#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_profiles() -> Result<()> {
+        let comments = include_str!("../../test_data/test_comments.json");
+        let mut lean = Sqlite::new_test(false)?;
+        let mut full = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                normalized: true,
+                comment_columns: ColumnSelection {
+                    profile: ColumnProfile::Full,
+                    columns: None,
+                },
+                ..Default::default()
+            },
+        )?;
+        for line in comments.lines() {
+            let comment: Comment = serde_json::from_str(line)?;
+            lean.insert_comment(comment.clone())?;
+            full.insert_comment(comment)?;
+        }
+        lean.commit()?;
+        full.commit()?;
+
+        assert!(
+            lean.connection
+                .prepare("SELECT body_sha1 FROM comment")
+                .is_err()
+        );
+
+        // The generated view exposes the extra columns of the fact table
+        let (updated, fullnames, controversiality): (i64, i64, i64) = full.connection.query_row(
+            "SELECT COUNT(updated_on), COUNT(author_fullname), COUNT(controversiality) \
+                 FROM comment",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        assert!(updated > 0);
+        assert!(fullnames > 0);
+        assert_eq!(controversiality as usize, comments.lines().count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_selected_columns() -> Result<()> {
+        let selection = ColumnSelection {
+            columns: Some(vec!["subreddit".to_string(), "body".to_string()]),
+            ..Default::default()
+        };
+        let connection = Connection::open_in_memory()?;
+        let mut storage = Sqlite::with_connection(
+            connection,
+            SqliteOptions {
+                comment_columns: selection.clone(),
+                ..Default::default()
+            },
+        )?;
+        let comments = include_str!("../../test_data/test_comments.json");
+        for line in comments.lines() {
+            storage.insert_comment(serde_json::from_str(line)?)?;
+        }
+        storage.commit()?;
+
+        let columns = existing_columns(&storage.connection, "comment")?;
+        assert_eq!(
+            columns,
+            [
+                "decoded_reddit_id",
+                "reddit_id",
+                "subreddit",
+                "body",
+                "created_utc"
+            ]
+        );
+        let count: i64 =
+            storage
+                .connection
+                .query_row("SELECT COUNT(body) FROM comment", [], |row| row.get(0))?;
+        assert_eq!(count as usize, comments.lines().count());
+
+        // Reopening with a different selection keeps writing the columns the table already has
+        let reopened = setup_content_table(
+            &storage.connection,
+            "comment",
+            COMMENT_COLUMNS,
+            &ColumnSelection {
+                columns: Some(vec!["score".to_string()]),
+                ..selection
+            },
+            &[],
+            &SqliteOptions::default(),
+        )?;
+        assert!(has_column(&reopened, "body"));
+        assert!(has_column(&reopened, "score"));
+        assert_eq!(
+            existing_columns(&storage.connection, "comment")?.len(),
+            columns.len() + 1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflict_policies() -> Result<()> {
+        let line = include_str!("../../test_data/test_comments.json")
+            .lines()
+            .next()
+            .unwrap();
+        let mut first: Comment = serde_json::from_str(line)?;
+        first.body = "first version".to_string();
+        first.score = Some(1);
+        first.retrieved_on = Some(100);
+
+        let import = |policy, retrieved_on, score| -> Result<(Option<i64>, String)> {
+            let mut second = first.clone();
+            second.body = "second version".to_string();
+            second.score = score;
+            second.retrieved_on = retrieved_on;
+            let mut storage = Sqlite::with_connection(
+                Connection::open_in_memory()?,
+                SqliteOptions {
+                    fts: true,
+                    on_conflict: policy,
+                    ..Default::default()
+                },
+            )?;
+            storage.insert_comment(first.clone())?;
+            storage.commit()?;
+            storage.insert_comment(second)?;
+            storage.commit()?;
+
+            let (score, body) =
+                storage
+                    .connection
+                    .query_row("SELECT score, body FROM comment", [], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })?;
+            // The update triggers keep the index in sync with the stored body
+            let indexed: i64 = storage.connection.query_row(
+                "SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH ?",
+                [if body == "first version" {
+                    "first"
+                } else {
+                    "second"
+                }],
+                |row| row.get(0),
+            )?;
+            let stale: i64 = storage.connection.query_row(
+                "SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH 'first OR second'",
+                [],
+                |row| row.get(0),
+            )?;
+            assert_eq!((indexed, stale), (1, 1));
+            Ok((score, body))
+        };
+
+        assert_eq!(
+            import(ConflictPolicy::KeepFirst, Some(200), Some(2))?,
+            (Some(1), "first version".to_string())
+        );
+        assert_eq!(
+            import(ConflictPolicy::KeepLatest, Some(50), Some(2))?,
+            (Some(1), "first version".to_string())
+        );
+        assert_eq!(
+            import(ConflictPolicy::KeepLatest, Some(200), Some(2))?,
+            (Some(2), "second version".to_string())
+        );
+        assert_eq!(
+            import(ConflictPolicy::Replace, Some(50), None)?,
+            (None, "second version".to_string())
+        );
+        assert_eq!(
+            import(ConflictPolicy::MergeNonNull, None, None)?,
+            (Some(1), "second version".to_string())
+        );
+
+        // Submission updates go through the submission update trigger
+        let line = include_str!("../../test_data/test_submissions.json")
+            .lines()
+            .next()
+            .unwrap();
+        let mut submission: Submission = serde_json::from_str(line)?;
+        submission.title = "first title".to_string();
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                fts: true,
+                on_conflict: ConflictPolicy::Replace,
+                ..Default::default()
+            },
+        )?;
+        storage.insert_submission(submission.clone())?;
+        storage.commit()?;
+        submission.title = "second title".to_string();
+        storage.insert_submission(submission)?;
+        storage.commit()?;
+        let indexed: i64 = storage.connection.query_row(
+            "SELECT COUNT(*) FROM submission_fts WHERE submission_fts MATCH 'second'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(indexed, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_revisions() -> Result<()> {
+        let line = include_str!("../../test_data/test_comments.json")
+            .lines()
+            .next()
+            .unwrap();
+        let first: Comment = serde_json::from_str(line)?;
+        let mut edited = first.clone();
+        edited.body = "edited".to_string();
+        edited.retrieved_on = Some(1_700_000_000);
+        edited.source_file = Some("RC_2023-11.zst".into());
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                revisions: true,
+                ..Default::default()
+            },
+        )?;
+        storage.insert_comment(first.clone())?;
+        storage.commit()?;
+        // An unchanged copy is not a revision, and a change within one batch is compared against the earlier item
+        storage.insert_comment(first.clone())?;
+        storage.insert_comment(edited.clone())?;
+        let mut removed = edited.clone();
+        removed.body = "[removed]".to_string();
+        removed.source_file = None;
+        storage.insert_comment(removed)?;
+        storage.commit()?;
+
+        let revisions: Vec<(Option<i64>, Option<String>, String, String)> = storage
+            .connection
+            .prepare(
+                "SELECT retrieved_on, source_file, changes, previous FROM comment_revision \
+                 ORDER BY revision_id",
+            )?
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].0, Some(1_700_000_000));
+        assert_eq!(revisions[0].1.as_deref(), Some("RC_2023-11.zst"));
+        assert_eq!(revisions[0].2, r#"{"body":"edited"}"#);
+        assert_eq!(
+            revisions[0].3,
+            serde_json::json!({ "body": first.body }).to_string()
+        );
+        assert_eq!(revisions[1].2, r#"{"body":"[removed]"}"#);
+        assert_eq!(revisions[1].3, r#"{"body":"edited"}"#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_revisions_from_later_dumps() -> Result<()> {
+        let line = include_str!("../../test_data/test_comments.json")
+            .lines()
+            .next()
+            .unwrap();
+        let first: Comment = serde_json::from_str(line)?;
+        let mut edited = first.clone();
+        edited.body = "edited".to_string();
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                revisions: true,
+                ..Default::default()
+            },
+        )?;
+        // The stored row keeps the first version, but a later dump repeating the edit is not another revision
+        for comment in [first.clone(), edited.clone(), edited] {
+            storage.insert_comment(comment)?;
+            storage.commit()?;
+        }
+        let revisions: i64 =
+            storage
+                .connection
+                .query_row("SELECT COUNT(*) FROM comment_revision", [], |row| {
+                    row.get(0)
+                })?;
+        assert_eq!(revisions, 1);
+
+        // Going back to the first version is compared against the edit
+        storage.insert_comment(first.clone())?;
+        storage.commit()?;
+        let previous: String = storage.connection.query_row(
+            "SELECT previous FROM comment_revision ORDER BY revision_id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(previous, r#"{"body":"edited"}"#);
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_base36_id() -> Result<()> {
         let mut storage = Sqlite::new_test(false)?;
@@ -541,6 +1623,327 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fts_consistency() -> Result<()> {
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                fts: true,
+                on_conflict: ConflictPolicy::Replace,
+                ..Default::default()
+            },
+        )?;
+        let comments = include_str!("../../test_data/test_comments.json");
+        let submissions = include_str!("../../test_data/test_submissions.json");
+        for line in comments.lines() {
+            storage.insert_comment(serde_json::from_str(line)?)?;
+        }
+        for line in submissions.lines() {
+            storage.insert_submission(serde_json::from_str(line)?)?;
+        }
+        storage.commit()?;
+
+        let check = |storage: &Sqlite| -> Result<()> {
+            // Compares each index against its content table, failing if they differ
+            for table in ["comment_fts", "submission_fts"] {
+                storage.connection.execute(
+                    &format!("INSERT INTO {table} ({table}, rank) VALUES ('integrity-check', 1)"),
+                    [],
+                )?;
+            }
+            Ok(())
+        };
+        check(&storage)?;
+
+        let mut comment: Comment = serde_json::from_str(comments.lines().next().unwrap())?;
+        comment.body = "zymurgy".to_string();
+        storage.insert_comment(comment)?;
+        let mut submission: Submission = serde_json::from_str(submissions.lines().next().unwrap())?;
+        submission.title = "xylophone".to_string();
+        storage.insert_submission(submission)?;
+        storage.commit()?;
+        check(&storage)?;
+        let matches: i64 = storage.connection.query_row(
+            "SELECT (SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH 'zymurgy') \
+             + (SELECT COUNT(*) FROM submission_fts WHERE submission_fts MATCH 'xylophone')",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(matches, 2);
+
+        storage
+            .connection
+            .execute_batch("DELETE FROM comment; DELETE FROM submission;")?;
+        check(&storage)?;
+        let remaining: i64 = storage.connection.query_row(
+            "SELECT COUNT(*) FROM submission_fts WHERE submission_fts MATCH 'xylophone'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(remaining, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fts_post_load() -> Result<()> {
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                fts: true,
+                fts_build: FtsBuild::PostLoad,
+                ..Default::default()
+            },
+        )?;
+        let comments = include_str!("../../test_data/test_comments.json");
+        for line in comments.lines() {
+            storage.insert_comment(serde_json::from_str(line)?)?;
+        }
+        storage.commit()?;
+        let triggers: i64 = storage.connection.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'trigger'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(triggers, 0);
+
+        storage.finish()?;
+        let indexed: i64 =
+            storage
+                .connection
+                .query_row("SELECT COUNT(*) FROM comment_fts", [], |row| row.get(0))?;
+        assert_eq!(indexed as usize, comments.lines().count());
+        // Later changes are indexed by the triggers installed after the build
+        storage.connection.execute(
+            "UPDATE comment SET body = 'zymurgy' WHERE rowid = (SELECT MIN(rowid) FROM comment)",
+            [],
+        )?;
+        let matches: i64 = storage.connection.query_row(
+            "SELECT COUNT(*) FROM comment_fts WHERE comment_fts MATCH 'zymurgy'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(matches, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fts_post_load_keeps_index() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let filename = directory.path().join("out.db");
+        let comments = include_str!("../../test_data/test_comments.json");
+        let mut comments = comments.lines();
+        let import = |fts_build, fts_index, line| -> Result<()> {
+            let mut storage = Sqlite::new(
+                &filename,
+                SqliteOptions {
+                    fts: true,
+                    fts_build,
+                    fts_index,
+                    ..Default::default()
+                },
+            )?;
+            storage.insert_comment(serde_json::from_str(line)?)?;
+            storage.finish()
+        };
+        import(
+            FtsBuild::Triggers,
+            FtsIndexOptions {
+                tokenizer: Some(FtsTokenizer::Trigram),
+                ..Default::default()
+            },
+            comments.next().unwrap(),
+        )?;
+        // Without index flags, a post-load build keeps the index the database has
+        import(
+            FtsBuild::PostLoad,
+            FtsIndexOptions::default(),
+            comments.next().unwrap(),
+        )?;
+
+        let connection = Connection::open(&filename)?;
+        let index = FtsIndex::existing(&connection)?.expect("an existing index");
+        assert_eq!(index.tokenizer, FtsTokenizer::Trigram);
+        let indexed: i64 =
+            connection.query_row("SELECT COUNT(*) FROM comment_fts", [], |row| row.get(0))?;
+        assert_eq!(indexed, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_input_file() -> Result<()> {
+        let mut storage = Sqlite::new_test(false)?;
+        let mut file = VerifiedFile {
+            path: "RC_2023-01.zst".into(),
+            size: 10,
+            sha256: "a".repeat(64),
+            expected: Some("b".repeat(64)),
+            status: VerificationStatus::Mismatch,
+        };
+        storage.record_input_file(&file)?;
+        file.expected = Some(file.sha256.clone());
+        file.status = VerificationStatus::Match;
+        storage.record_input_file(&file)?;
+        assert_eq!(storage.in_transaction, 0);
+
+        let (count, verified): (i64, bool) = storage.connection.query_row(
+            "SELECT COUNT(*), MAX(verified) FROM input_file",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(count, 1);
+        assert!(verified);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_provenance() -> Result<()> {
+        let mut storage = Sqlite::new_test(false)?;
+        let mut provenance = Provenance::new(
+            Filter::default(),
+            vec![InputFile {
+                path: "RC_2023-01.zst".to_string(),
+                size: 10,
+                sha256: None,
+            }],
+            1_700_000_000,
+        );
+        provenance.item_counts.insert("comment", 3);
+        assert_eq!(storage.record_provenance(&provenance)?, 1);
+        assert_eq!(storage.record_provenance(&provenance)?, 2);
+        assert_eq!(storage.in_transaction, 0);
+
+        let value = |key: &str| -> Result<Option<String>> {
+            Ok(storage.connection.query_row(
+                "SELECT value FROM metadata WHERE import_id = 2 AND key = ?",
+                [key],
+                |row| row.get(0),
+            )?)
+        };
+        assert_eq!(
+            value("importer_version")?.as_deref(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        assert_eq!(value("started_utc")?.as_deref(), Some("1700000000"));
+        assert_eq!(value("item_counts")?.as_deref(), Some(r#"{"comment":3}"#));
+        let input_files: serde_json::Value =
+            serde_json::from_str(&value("input_files")?.unwrap_or_default())?;
+        assert_eq!(input_files[0]["size"], 10);
+        let filter: serde_json::Value =
+            serde_json::from_str(&value("filter")?.unwrap_or_default())?;
+        assert_eq!(filter["users"], serde_json::json!([]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflict_policy_awards() -> Result<()> {
+        let line = include_str!("../../test_data/test_comments.json")
+            .lines()
+            .next()
+            .unwrap();
+        let mut comment_json: serde_json::Value = serde_json::from_str(line)?;
+        comment_json["all_awardings"] = serde_json::json!([
+            {"id": "gid_2", "name": "Gold", "count": 1},
+            {"id": "award_5f123e3d-4f48-42f4-9c11-e98b566d5897", "name": "Wholesome", "count": 3}
+        ]);
+        comment_json["retrieved_on"] = 100.into();
+        let first: Comment = serde_json::from_value(comment_json.clone())?;
+        // A re-scrape with more gold, which no longer lists the other award
+        comment_json["all_awardings"] =
+            serde_json::json!([{"id": "gid_2", "name": "Gold", "count": 2}]);
+        let rescraped: Comment = serde_json::from_value(comment_json)?;
+
+        let import = |policy, retrieved_on, same_batch: bool| -> Result<Vec<(String, i64)>> {
+            let mut second = rescraped.clone();
+            second.retrieved_on = Some(retrieved_on);
+            let mut storage = Sqlite::with_connection(
+                Connection::open_in_memory()?,
+                SqliteOptions {
+                    on_conflict: policy,
+                    ..Default::default()
+                },
+            )?;
+            storage.insert_comment(first.clone())?;
+            if !same_batch {
+                storage.commit()?;
+            }
+            storage.insert_comment(second)?;
+            storage.commit()?;
+
+            let mut stmt = storage
+                .connection
+                .prepare("SELECT award_id, count FROM comment_award ORDER BY award_id")?;
+            let counts = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<_, _>>()?;
+            Ok(counts)
+        };
+        let first_counts = vec![
+            ("award_5f123e3d-4f48-42f4-9c11-e98b566d5897".to_string(), 3),
+            ("gid_2".to_string(), 1),
+        ];
+        let second_counts = vec![("gid_2".to_string(), 2)];
+
+        assert_eq!(import(ConflictPolicy::KeepFirst, 200, false)?, first_counts);
+        assert_eq!(import(ConflictPolicy::KeepLatest, 50, false)?, first_counts);
+        assert_eq!(import(ConflictPolicy::KeepLatest, 50, true)?, first_counts);
+        assert_eq!(
+            import(ConflictPolicy::KeepLatest, 200, false)?,
+            second_counts
+        );
+        assert_eq!(
+            import(ConflictPolicy::KeepLatest, 200, true)?,
+            second_counts
+        );
+        assert_eq!(import(ConflictPolicy::Replace, 50, false)?, second_counts);
+        assert_eq!(
+            import(ConflictPolicy::MergeNonNull, 50, false)?,
+            vec![first_counts[0].clone(), second_counts[0].clone()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_json() -> Result<()> {
+        let mut storage = Sqlite::with_connection(
+            Connection::open_in_memory()?,
+            SqliteOptions {
+                raw_json: true,
+                ..Default::default()
+            },
+        )?;
+        let comments = include_str!("../../test_data/test_comments.json");
+        let submissions = include_str!("../../test_data/test_submissions.json");
+
+        let comment_line = comments.lines().next().unwrap();
+        let mut comment: Comment = serde_json::from_str(comment_line)?;
+        comment.raw_json = Some(RawJsonFormat::Plain.encode(comment_line)?);
+        storage.insert_comment(comment)?;
+
+        let submission_line = submissions.lines().next().unwrap();
+        let mut submission: Submission = serde_json::from_str(submission_line)?;
+        submission.raw_json = Some(RawJsonFormat::Zstd.encode(submission_line)?);
+        storage.insert_submission(submission)?;
+        storage.commit()?;
+
+        let raw_comment: String =
+            storage
+                .connection
+                .query_row("SELECT raw_json FROM comment", [], |row| row.get(0))?;
+        assert_eq!(raw_comment, comment_line);
+
+        let raw_submission: Vec<u8> =
+            storage
+                .connection
+                .query_row("SELECT raw_json FROM submission", [], |row| row.get(0))?;
+        let raw_submission = zstd::bulk::decompress(&raw_submission, submission_line.len())?;
+        assert_eq!(raw_submission, submission_line.as_bytes());
+
+        Ok(())
+    }
+
     #[test]
     fn test_batch_size_flushing() -> Result<()> {
         let mut storage = Sqlite::new_test(false)?;
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use rusqlite::{
+    Connection, OptionalExtension,
+    types::{ToSqlOutput, Value},
+};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::sqlite::columns::Column;
+
+/// Comment fields whose changes between dumps are recorded.
+pub const COMMENT_TRACKED: &[&str] = &[
+    "author",
+    "body",
+    "score",
+    "edited_utc",
+    "distinguished",
+    "stickied",
+    "locked",
+];
+
+/// Submission fields whose changes between dumps are recorded.
+pub const SUBMISSION_TRACKED: &[&str] = &[
+    "author",
+    "title",
+    "selftext",
+    "url",
+    "score",
+    "num_comments",
+    "removed_by_category",
+    "over_18",
+    "edited_utc",
+];
+
+/// Records the versions of items that differ from the latest version in a tracked field.
+pub struct RevisionWriter<T: 'static> {
+    /// The table, or view with the normalised schema, holding the stored rows
+    table: &'static str,
+    revision_table: &'static str,
+    tracked: Vec<&'static Column<T>>,
+}
+
+impl<T> RevisionWriter<T> {
+    pub fn new(
+        registry: &'static [Column<T>],
+        tracked: &[&str],
+        table: &'static str,
+        revision_table: &'static str,
+    ) -> Self {
+        RevisionWriter {
+            table,
+            revision_table,
+            tracked: registry
+                .iter()
+                .filter(|column| tracked.contains(&column.name))
+                .collect(),
+        }
+    }
+
+    /// Record `item` if it differs from the latest version of the item with the same id.
+    ///
+    /// The latest version is the stored row with the changes of its recorded revisions applied, as the policy may
+    /// have kept the row as it was. `earlier` is an item with the same id from the batch being written, which is
+    /// compared against instead as it has not been stored yet.
+    pub fn record(
+        &self,
+        connection: &Connection,
+        decoded_reddit_id: i64,
+        item: &T,
+        earlier: Option<&T>,
+        retrieved_on: Option<i64>,
+        source_file: Option<&str>,
+    ) -> Result<()> {
+        let previous = match earlier {
+            Some(earlier) => self.values(earlier)?,
+            None => {
+                let names: Vec<_> = self.tracked.iter().map(|column| column.name).collect();
+                let stored: Option<Vec<Value>> = connection
+                    .prepare_cached(&format!(
+                        "SELECT {} FROM {} WHERE decoded_reddit_id = ?",
+                        names.join(", "),
+                        self.table
+                    ))?
+                    .query_row([decoded_reddit_id], |row| {
+                        (0..names.len()).map(|index| row.get(index)).collect()
+                    })
+                    .optional()?;
+                match stored {
+                    Some(mut stored) => {
+                        self.apply_revisions(connection, decoded_reddit_id, &mut stored)?;
+                        stored
+                    }
+                    None => return Ok(()),
+                }
+            }
+        };
+
+        let mut changes = Map::new();
+        let mut replaced = Map::new();
+        for ((column, incoming), stored) in
+            self.tracked.iter().zip(self.values(item)?).zip(previous)
+        {
+            if incoming != stored {
+                changes.insert(column.name.to_string(), json_value(incoming));
+                replaced.insert(column.name.to_string(), json_value(stored));
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+        connection
+            .prepare_cached(&format!(
+                "INSERT INTO {} (decoded_reddit_id, retrieved_on, source_file, changes, previous) \
+                 VALUES (?, ?, ?, ?, ?)",
+                self.revision_table
+            ))?
+            .execute(rusqlite::params![
+                decoded_reddit_id,
+                retrieved_on,
+                source_file,
+                JsonValue::Object(changes).to_string(),
+                JsonValue::Object(replaced).to_string(),
+            ])?;
+        Ok(())
+    }
+
+    /// Apply the changes of the revisions recorded for an item, in the order they were recorded, to its stored
+    /// `values`.
+    fn apply_revisions(
+        &self,
+        connection: &Connection,
+        decoded_reddit_id: i64,
+        values: &mut [Value],
+    ) -> Result<()> {
+        let mut stmt = connection.prepare_cached(&format!(
+            "SELECT changes FROM {} WHERE decoded_reddit_id = ? ORDER BY revision_id",
+            self.revision_table
+        ))?;
+        let mut rows = stmt.query([decoded_reddit_id])?;
+        while let Some(row) = rows.next()? {
+            let changes: Map<String, JsonValue> = serde_json::from_str(&row.get::<_, String>(0)?)?;
+            for (column, value) in self.tracked.iter().zip(values.iter_mut()) {
+                if let Some(changed) = changes.get(column.name) {
+                    *value = sql_value(changed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The values of the tracked fields of `item`, as they would be stored.
+    fn values(&self, item: &T) -> rusqlite::Result<Vec<Value>> {
+        self.tracked
+            .iter()
+            .map(|column| {
+                Ok(match (column.value)(item)? {
+                    ToSqlOutput::Borrowed(value) => value.into(),
+                    ToSqlOutput::Owned(value) => value,
+                    _ => Value::Null,
+                })
+            })
+            .collect()
+    }
+}
+
+fn json_value(value: Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Integer(value) => value.into(),
+        Value::Real(value) => value.into(),
+        Value::Text(value) => value.into(),
+        Value::Blob(value) => value.into(),
+    }
+}
+
+/// The inverse of [`json_value`].
+fn sql_value(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(value) => Value::Integer(i64::from(*value)),
+        JsonValue::Number(number) => match number.as_i64() {
+            Some(value) => Value::Integer(value),
+            None => Value::Real(number.as_f64().unwrap_or_default()),
+        },
+        JsonValue::String(value) => Value::Text(value.clone()),
+        JsonValue::Array(bytes) => Value::Blob(
+            bytes
+                .iter()
+                .filter_map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                .collect(),
+        ),
+        JsonValue::Object(_) => Value::Text(value.to_string()),
+    }
+}
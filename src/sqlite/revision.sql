@@ -0,0 +1,17 @@
+-- Versions of comments and submissions that differ from the latest version seen in a tracked field, kept with
+-- --revisions. The latest version is the stored row with the changes of its earlier revisions applied. changes holds
+-- the differing fields of the incoming version, and previous the values it was compared against, as JSON objects. source_file is the input file the incoming version was read from.
+CREATE TABLE IF NOT EXISTS comment_revision (revision_id INTEGER PRIMARY KEY,
+                                             decoded_reddit_id INTEGER NOT NULL,
+                                             retrieved_on INTEGER,
+                                             source_file TEXT,
+                                             changes TEXT NOT NULL,
+                                             previous TEXT NOT NULL);
+CREATE INDEX IF NOT EXISTS comment_revision_decoded_reddit_id ON comment_revision (decoded_reddit_id);
+CREATE TABLE IF NOT EXISTS submission_revision (revision_id INTEGER PRIMARY KEY,
+                                                decoded_reddit_id INTEGER NOT NULL,
+                                                retrieved_on INTEGER,
+                                                source_file TEXT,
+                                                changes TEXT NOT NULL,
+                                                previous TEXT NOT NULL);
+CREATE INDEX IF NOT EXISTS submission_revision_decoded_reddit_id ON submission_revision (decoded_reddit_id);
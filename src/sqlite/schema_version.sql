@@ -0,0 +1,4 @@
+-- The migrations applied to the database. A database created by the importer starts at its current version.
+CREATE TABLE IF NOT EXISTS schema_version (version INTEGER PRIMARY KEY,
+                                           description TEXT NOT NULL,
+                                           applied_utc INTEGER NOT NULL);
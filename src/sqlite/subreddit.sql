@@ -0,0 +1,19 @@
+-- decoded_subreddit_id is the decoded base36 id of the subreddit, the same value stored as decoded_subreddit_id in the
+-- comment and submission tables. With the normalised schema, rows are also created for every subreddit content refers to;
+-- these only have display_name set (and a negative decoded_subreddit_id if the content had no subreddit id) until
+-- the subreddit's metadata is imported.
+CREATE TABLE IF NOT EXISTS subreddit (decoded_subreddit_id INTEGER PRIMARY KEY,
+                                      reddit_id TEXT,
+                                      display_name TEXT NOT NULL,
+                                      title TEXT,
+                                      public_description TEXT,
+                                      description TEXT,
+                                      subscribers INTEGER,
+                                      created_utc INTEGER,
+                                      over_18 BOOLEAN,
+                                      subreddit_type TEXT,
+                                      lang TEXT,
+                                      url TEXT,
+                                      retrieved_on INTEGER,
+                                      raw_json BLOB);
+CREATE INDEX IF NOT EXISTS subreddit_display_name ON subreddit (display_name);
@@ -1,11 +1,40 @@
-use crate::reddit_types::{comment::Comment, submission::Submission};
+use std::sync::Arc;
+
+use crate::reddit_types::{
+    RawJson, comment::Comment, submission::Submission, subreddit::Subreddit,
+};
 use anyhow::Result;
+use clap::ValueEnum;
+
+/// Where imported items are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// A Sqlite database
+    #[default]
+    Sqlite,
+    /// A directory of Parquet files, with a comment and a submission directory. Subreddit metadata is not supported.
+    Parquet,
+    /// A directory of compressed JSON Lines files, one for each kind of item
+    Jsonl,
+}
 
 pub trait Storage: Sized {
     fn insert_comment(&mut self, comment: Comment) -> Result<usize>;
     fn insert_submission(&mut self, submission: Submission) -> Result<usize>;
+    fn insert_subreddit(&mut self, subreddit: Subreddit) -> Result<usize>;
+    /// Complete the import once every item has been inserted.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait Storable: Sized {
+    /// Whether lines can be rejected with a `FilterView` before they are deserialized. The view reads the fields
+    /// comments and submissions share, which other kinds of item may not have.
+    const PRE_FILTER: bool = true;
+
     fn store<T: Storage>(self, storage: &mut T) -> Result<usize>;
+    fn set_raw_json(&mut self, raw_json: RawJson);
+    /// Record the input file the item was read from. Only comments and submissions keep it.
+    fn set_source_file(&mut self, _source_file: Arc<str>) {}
 }